@@ -1,11 +1,10 @@
-use std::fs::File;
 use std::path::PathBuf;
 
 use futures::prelude::*;
 use serde::de;
 use serde::Deserialize;
 use tokio01::runtime::current_thread::block_on_all as block_on_all01;
-use twitter_stream::Credentials;
+use twitter_stream::Token;
 
 #[derive(Deserialize)]
 #[serde(untagged)]
@@ -39,35 +38,6 @@ struct User {
     screen_name: String,
 }
 
-#[derive(Deserialize)]
-#[serde(remote = "twitter_stream::Token")]
-struct TokenDef {
-    #[serde(flatten)]
-    #[serde(with = "Consumer")]
-    client: Credentials,
-    #[serde(flatten)]
-    #[serde(with = "Access")]
-    token: Credentials,
-}
-
-#[derive(Deserialize)]
-#[serde(remote = "Credentials")]
-struct Consumer {
-    #[serde(rename = "consumer_key")]
-    identifier: String,
-    #[serde(rename = "consumer_secret")]
-    secret: String,
-}
-
-#[derive(Deserialize)]
-#[serde(remote = "Credentials")]
-struct Access {
-    #[serde(rename = "access_key")]
-    identifier: String,
-    #[serde(rename = "access_secret")]
-    secret: String,
-}
-
 #[tokio::main]
 async fn main() {
     const TRACK: &str = "@NAME_OF_YOUR_ACCOUNT";
@@ -76,8 +46,7 @@ async fn main() {
     credential_path.pop();
     credential_path.push("credential.json");
 
-    let credential = File::open(credential_path).unwrap();
-    let token = TokenDef::deserialize(&mut json::Deserializer::from_reader(credential)).unwrap();
+    let token: Token = twitter_stream::config::from_file(credential_path).unwrap();
 
     let stream = twitter_stream::Builder::filter(token.as_ref())
         .track(Some(TRACK))
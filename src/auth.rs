@@ -0,0 +1,979 @@
+//! Authentication methods for requests to the Streaming API.
+
+mod pool;
+#[cfg(feature = "zeroize")]
+mod secret;
+pub mod sign_in;
+
+pub use self::pool::{PoolHealth, TokenLease, TokenPool, TokenStatus};
+#[cfg(feature = "zeroize")]
+pub use self::secret::Secret;
+
+use std::borrow::Borrow;
+use std::fmt::{self, Debug, Display, Formatter, Write};
+use std::future::Future;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use http::header::{
+    HeaderMap, HeaderName, HeaderValue, InvalidHeaderValue, AUTHORIZATION, CONTENT_LENGTH,
+    CONTENT_TYPE,
+};
+use http::{Request, Response};
+use http_body::Body;
+use oauth::signature_method::{HmacSha1, Sign, SignatureMethod};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+#[cfg(feature = "serde")]
+use serde::{de, Deserializer, Serialize};
+use tower_service::Service;
+
+use crate::error::Error;
+use crate::types::{RequestMethod, StatusCode, Uri};
+use crate::util::{basic_auth, read_body, Hidden};
+use crate::{Credentials, Token};
+
+/// A method of authenticating requests made by a `Builder`.
+///
+/// This crate provides implementations for OAuth 1.0a user context (`Token`),
+/// OAuth 2.0 bearer tokens (`BearerToken`), HTTP Basic authentication (`BasicAuth`) and
+/// unauthenticated requests (`NoAuth`). Any other scheme can be plugged in by implementing
+/// this trait or with the `from_fn` function.
+pub trait Authorizer {
+    /// Returns the headers to be added to a request to `uri` with the given `method`.
+    ///
+    /// `uri` does not contain a query part. `params` is the request parameters encoded as
+    /// an `application/x-www-form-urlencoded` string, percent-encoded as in
+    /// [RFC 3986][rfc] (e.g. `follow=12%2C13&track=Rust`), which is sent either as
+    /// the query string or as the request body.
+    ///
+    /// [rfc]: https://tools.ietf.org/html/rfc3986#section-2.1
+    fn authorize(&self, method: &RequestMethod, uri: &Uri, params: &str) -> HeaderMap;
+
+    /// Same as `authorize`, except that the current time is taken to be `clock_offset` seconds
+    /// ahead of the local clock.
+    ///
+    /// This matters for the schemes that sign a timestamp, like `Token`. The default
+    /// implementation ignores the offset.
+    fn authorize_with_clock_offset(
+        &self,
+        method: &RequestMethod,
+        uri: &Uri,
+        params: &str,
+        clock_offset: i64,
+    ) -> HeaderMap {
+        let _ = clock_offset;
+        self.authorize(method, uri, params)
+    }
+
+    /// Returns an owned copy of `self` if its headers depend on the local clock.
+    ///
+    /// The copy is used to sign a request again after the server has rejected it because of
+    /// a clock skew. The default implementation returns `None`, which disables the retry.
+    fn clock_dependent(&self) -> Option<Box<dyn Authorizer + Send + Sync>> {
+        None
+    }
+
+    /// Returns the inputs of the signature that `authorize_with_clock_offset` would make with
+    /// the same arguments, for inspecting why a request was rejected.
+    ///
+    /// Schemes that do not sign requests return `None`, which is the default.
+    fn signature_base(
+        &self,
+        method: &RequestMethod,
+        uri: &Uri,
+        params: &str,
+        clock_offset: i64,
+    ) -> Option<SignatureBase> {
+        let _ = (method, uri, params, clock_offset);
+        None
+    }
+
+    /// Returns an `Authorizer` to be used for a single connection instead of `self`.
+    ///
+    /// An authorizer that chooses among several credentials (like `TokenPool`) returns one
+    /// that sticks to the chosen credentials, so that the request, a retry of it and
+    /// the outcome of the connection (see `report`) all concern the same credentials.
+    /// The default implementation returns `None`, which means `self` is used.
+    fn for_connection(&self) -> Option<Box<dyn Authorizer + Send + Sync>> {
+        None
+    }
+
+    /// Called with the outcome of a connection whose request was authorized by `self`.
+    ///
+    /// This is only called on the authorizers returned by `for_connection`.
+    /// The default implementation does nothing.
+    fn report(&self, event: ConnectionEvent) {
+        let _ = event;
+    }
+}
+
+/// The outcome of a connection, which is reported with `Authorizer::report`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// The server accepted the request.
+    Connected,
+    /// The server rejected the request with the status code.
+    Rejected(StatusCode),
+    /// The server sent a `disconnect` message with the `code`
+    /// (e.g. `{"disconnect":{"code":6,"stream_name":"...","reason":"..."}}`).
+    ///
+    /// See the [Twitter Developer Documentation][1] for the list of the codes.
+    ///
+    /// [1]: https://developer.twitter.com/en/docs/tweets/filter-realtime/guides/streaming-message-types
+    Disconnected(u64),
+}
+
+/// The inputs and the result of an OAuth 1.0a signature.
+///
+/// See [RFC 5849 section 3.4.1.][rfc] for the format of the strings.
+///
+/// [rfc]: https://tools.ietf.org/html/rfc5849#section-3.4.1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignatureBase {
+    /// The signature base string, e.g.
+    /// `GET&https%3A%2F%2Fstream.twitter.com%2F1.1%2Fstatuses%2Fsample.json&oauth_consumer_key%3D...`.
+    pub base_string: String,
+    /// The normalized request parameters including the `oauth_*` parameters, e.g.
+    /// `oauth_consumer_key=...&oauth_nonce=...&track=Rust`.
+    pub normalized_parameters: String,
+    /// The value of the `Authorization` header carrying the signature of `base_string`.
+    pub authorization: String,
+}
+
+/// An OAuth 1.0a `Token` that signs every request with the given nonce and timestamp.
+///
+/// The signatures made with this are reproducible, which is useful for golden tests and
+/// for checking a signature against another implementation. Twitter rejects a nonce that
+/// has been used before, so this should not be used to connect to the API.
+#[derive(Clone)]
+pub struct Deterministic<C = String, A = String> {
+    pub token: Token<C, A>,
+    pub nonce: String,
+    pub timestamp: u64,
+}
+
+/// Credentials for HTTP Basic authentication.
+///
+/// The `Debug` output leaves out the password.
+#[derive(Copy, Clone)]
+pub struct BasicAuth<U = String, P = String> {
+    pub username: U,
+    pub password: P,
+}
+
+/// An `Authorizer` that does not add any header.
+///
+/// This is useful if the requests are routed through a gateway that authenticates them.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoAuth;
+
+/// An `Authorizer` that calls a function to authorize requests.
+///
+/// This is created by the `from_fn` function.
+#[derive(Copy, Clone, Debug)]
+pub struct FromFn<F>(F);
+
+/// Create an `Authorizer` from a function with the same signature as `Authorizer::authorize`.
+pub fn from_fn<F>(f: F) -> FromFn<F>
+where
+    F: Fn(&RequestMethod, &Uri, &str) -> HeaderMap,
+{
+    FromFn(f)
+}
+
+/// An OAuth 2.0 bearer token used for app-only authentication.
+///
+/// See the [Twitter Developer Documentation][1] for more information.
+///
+/// The `Debug` output leaves out the token. With the `serde` feature, the token is
+/// (de)serialized as a plain string, and deserializing a token that cannot be sent in
+/// a header (see `try_new`) fails.
+///
+/// [1]: https://developer.twitter.com/en/docs/basics/authentication/oauth-2-0/application-only
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(transparent))]
+pub struct BearerToken<T = String> {
+    token: T,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token_type: String,
+    access_token: String,
+}
+
+const TOKEN_URI: &str = "https://api.twitter.com/oauth2/token";
+
+impl<T: Borrow<str>> BearerToken<T> {
+    /// # Panics
+    ///
+    /// This will panic if `token` cannot be sent in a header (see `try_new`).
+    pub fn new(token: T) -> Self {
+        Self::try_new(token).expect("invalid bearer token")
+    }
+
+    /// Same as `new` except that it returns an error instead of panicking if `token`
+    /// contains characters that are not allowed in a header value (e.g. a newline).
+    pub fn try_new(token: T) -> Result<Self, InvalidHeaderValue> {
+        HeaderValue::from_str(token.borrow())?;
+        Ok(BearerToken { token })
+    }
+
+    /// Returns the token string.
+    pub fn token(&self) -> &str {
+        self.token.borrow()
+    }
+
+    /// Borrow the token string from `self` and make a new `BearerToken` with it.
+    pub fn as_ref(&self) -> BearerToken<&str> {
+        BearerToken {
+            token: self.token.borrow(),
+        }
+    }
+}
+
+impl BearerToken {
+    /// Read the token from the `TWITTER_BEARER_TOKEN` environment variable.
+    ///
+    /// Leading and trailing whitespace (e.g. a newline from a secrets file) is removed.
+    ///
+    /// This requires the `serde` feature.
+    #[cfg(feature = "serde")]
+    pub fn from_env() -> Result<Self, crate::config::Error> {
+        const NAME: &str = "TWITTER_BEARER_TOKEN";
+        let token = crate::config::required_var(NAME)?.trim().to_owned();
+        BearerToken::try_new(token).map_err(|_| crate::config::Error::InvalidVar(NAME.into()))
+    }
+
+    /// Obtain a bearer token by exchanging the client credentials (consumer key and secret)
+    /// at the `POST oauth2/token` endpoint, using `client` to make the HTTP request.
+    ///
+    /// See the [Twitter Developer Documentation][1] for more information.
+    ///
+    /// [1]: https://developer.twitter.com/en/docs/basics/authentication/api-reference/token
+    ///
+    /// # Panics
+    ///
+    /// This will call `<S as Service>::call` without checking for `<S as Service>::poll_ready`
+    /// and may cause a panic if `client` is not ready to send an HTTP request yet.
+    pub fn obtain<C, S, ReqB, ResB>(
+        client_credentials: Credentials<C>,
+        client: S,
+    ) -> impl Future<Output = Result<Self, Error<S::Error>>>
+    where
+        C: Borrow<str>,
+        S: Service<Request<ReqB>, Response = Response<ResB>>,
+        ReqB: From<Vec<u8>>,
+        ResB: Body<Error = S::Error>,
+    {
+        Self::obtain_from(Uri::from_static(TOKEN_URI), client_credentials, client)
+    }
+
+    /// Same as `obtain` except that it makes the request to `endpoint`
+    /// instead of the default `https://api.twitter.com/oauth2/token`.
+    pub fn obtain_from<C, S, ReqB, ResB>(
+        endpoint: Uri,
+        client_credentials: Credentials<C>,
+        mut client: S,
+    ) -> impl Future<Output = Result<Self, Error<S::Error>>>
+    where
+        C: Borrow<str>,
+        S: Service<Request<ReqB>, Response = Response<ResB>>,
+        ReqB: From<Vec<u8>>,
+        ResB: Body<Error = S::Error>,
+    {
+        // The credentials are URL-encoded according to RFC 1738 before being joined.
+        let key = utf8_percent_encode(client_credentials.identifier(), NON_ALPHANUMERIC);
+        let secret = utf8_percent_encode(client_credentials.secret(), NON_ALPHANUMERIC);
+        let data = b"grant_type=client_credentials".to_vec();
+
+        let req = Request::post(endpoint)
+            .header(
+                AUTHORIZATION,
+                basic_auth(&key.to_string(), &secret.to_string()),
+            )
+            .header(
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/x-www-form-urlencoded;charset=UTF-8"),
+            )
+            .header(CONTENT_LENGTH, data.len())
+            .body(data.into())
+            .unwrap();
+        let response = client.call(req);
+
+        async move {
+            let res = response.await.map_err(Error::Service)?;
+            if StatusCode::OK != res.status() {
+                return Err(Error::Http(res.status()));
+            }
+
+            let body = read_body(res.into_body()).await?;
+            let res: TokenResponse = json::from_slice(&body).map_err(Error::custom)?;
+            if !res.token_type.eq_ignore_ascii_case("bearer") {
+                let msg = format!("unexpected token type: {}", res.token_type);
+                return Err(Error::custom(msg));
+            }
+
+            BearerToken::try_new(res.access_token).map_err(Error::custom)
+        }
+    }
+}
+
+impl<U: Borrow<str>, P: Borrow<str>> BasicAuth<U, P> {
+    pub fn new(username: U, password: P) -> Self {
+        BasicAuth { username, password }
+    }
+
+    /// Borrow the strings from `self` and make a new `BasicAuth` with them.
+    pub fn as_ref(&self) -> BasicAuth<&str, &str> {
+        BasicAuth::new(self.username.borrow(), self.password.borrow())
+    }
+}
+
+impl<C: Borrow<str>, A: Borrow<str>> Deterministic<C, A> {
+    pub fn new(token: Token<C, A>, nonce: impl Into<String>, timestamp: u64) -> Self {
+        Deterministic {
+            token,
+            nonce: nonce.into(),
+            timestamp,
+        }
+    }
+
+    fn options(&self) -> oauth::Options<'_> {
+        let mut options = oauth::Options::new();
+        options.nonce(&*self.nonce).timestamp(self.timestamp);
+        options
+    }
+}
+
+impl<A: Authorizer + ?Sized> Authorizer for &A {
+    fn authorize(&self, method: &RequestMethod, uri: &Uri, params: &str) -> HeaderMap {
+        (**self).authorize(method, uri, params)
+    }
+
+    fn authorize_with_clock_offset(
+        &self,
+        method: &RequestMethod,
+        uri: &Uri,
+        params: &str,
+        clock_offset: i64,
+    ) -> HeaderMap {
+        (**self).authorize_with_clock_offset(method, uri, params, clock_offset)
+    }
+
+    fn clock_dependent(&self) -> Option<Box<dyn Authorizer + Send + Sync>> {
+        (**self).clock_dependent()
+    }
+
+    fn for_connection(&self) -> Option<Box<dyn Authorizer + Send + Sync>> {
+        (**self).for_connection()
+    }
+
+    fn report(&self, event: ConnectionEvent) {
+        (**self).report(event)
+    }
+
+    fn signature_base(
+        &self,
+        method: &RequestMethod,
+        uri: &Uri,
+        params: &str,
+        clock_offset: i64,
+    ) -> Option<SignatureBase> {
+        (**self).signature_base(method, uri, params, clock_offset)
+    }
+}
+
+impl<A: Authorizer + ?Sized> Authorizer for Box<A> {
+    fn authorize(&self, method: &RequestMethod, uri: &Uri, params: &str) -> HeaderMap {
+        (**self).authorize(method, uri, params)
+    }
+
+    fn authorize_with_clock_offset(
+        &self,
+        method: &RequestMethod,
+        uri: &Uri,
+        params: &str,
+        clock_offset: i64,
+    ) -> HeaderMap {
+        (**self).authorize_with_clock_offset(method, uri, params, clock_offset)
+    }
+
+    fn clock_dependent(&self) -> Option<Box<dyn Authorizer + Send + Sync>> {
+        (**self).clock_dependent()
+    }
+
+    fn for_connection(&self) -> Option<Box<dyn Authorizer + Send + Sync>> {
+        (**self).for_connection()
+    }
+
+    fn report(&self, event: ConnectionEvent) {
+        (**self).report(event)
+    }
+
+    fn signature_base(
+        &self,
+        method: &RequestMethod,
+        uri: &Uri,
+        params: &str,
+        clock_offset: i64,
+    ) -> Option<SignatureBase> {
+        (**self).signature_base(method, uri, params, clock_offset)
+    }
+}
+
+impl<C: Borrow<str>, A: Borrow<str>> Authorizer for Token<C, A> {
+    fn authorize(&self, method: &RequestMethod, uri: &Uri, params: &str) -> HeaderMap {
+        let options = oauth::Options::new();
+        let authorization = oauth1_sign(self, method, uri, params, options, HmacSha1);
+        header(AUTHORIZATION, authorization)
+    }
+
+    fn authorize_with_clock_offset(
+        &self,
+        method: &RequestMethod,
+        uri: &Uri,
+        params: &str,
+        clock_offset: i64,
+    ) -> HeaderMap {
+        let mut options = oauth::Options::new();
+        options.timestamp(timestamp(clock_offset));
+        let authorization = oauth1_sign(self, method, uri, params, options, HmacSha1);
+        header(AUTHORIZATION, authorization)
+    }
+
+    fn clock_dependent(&self) -> Option<Box<dyn Authorizer + Send + Sync>> {
+        let token = Token::new(
+            OwnedSecret::from(self.client.identifier()),
+            OwnedSecret::from(self.client.secret()),
+            OwnedSecret::from(self.token.identifier()),
+            OwnedSecret::from(self.token.secret()),
+        );
+        Some(Box::new(token))
+    }
+
+    fn signature_base(
+        &self,
+        method: &RequestMethod,
+        uri: &Uri,
+        params: &str,
+        clock_offset: i64,
+    ) -> Option<SignatureBase> {
+        let mut options = oauth::Options::new();
+        options.timestamp(timestamp(clock_offset));
+        Some(oauth1_signature_base(self, method, uri, params, options))
+    }
+}
+
+impl<C: Borrow<str>, A: Borrow<str>> Authorizer for Deterministic<C, A> {
+    fn authorize(&self, method: &RequestMethod, uri: &Uri, params: &str) -> HeaderMap {
+        let options = self.options();
+        let authorization = oauth1_sign(&self.token, method, uri, params, options, HmacSha1);
+        header(AUTHORIZATION, authorization)
+    }
+
+    fn signature_base(
+        &self,
+        method: &RequestMethod,
+        uri: &Uri,
+        params: &str,
+        _: i64,
+    ) -> Option<SignatureBase> {
+        let options = self.options();
+        Some(oauth1_signature_base(
+            &self.token,
+            method,
+            uri,
+            params,
+            options,
+        ))
+    }
+}
+
+/// The string type of the copy of a `Token` kept for a clock skew retry,
+/// which is zeroed on drop if possible.
+#[cfg(feature = "zeroize")]
+type OwnedSecret = Secret;
+#[cfg(not(feature = "zeroize"))]
+type OwnedSecret = String;
+
+/// Returns the current UNIX time adjusted by `clock_offset` seconds.
+fn timestamp(clock_offset: i64) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    now.saturating_add(clock_offset).max(0) as u64
+}
+
+/// Makes an OAuth 1.0a `Authorization` header value with the given signature method.
+fn oauth1_sign<'a, C, A, SM>(
+    token: &'a Token<C, A>,
+    method: &RequestMethod,
+    uri: &Uri,
+    params: &str,
+    mut options: oauth::Options<'a>,
+    signature_method: SM,
+) -> String
+where
+    C: Borrow<str>,
+    A: Borrow<str>,
+    SM: SignatureMethod,
+{
+    // The parameters must be appended to the signature base string in byte order.
+    let mut pairs: Vec<(&str, &str)> = params
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut kv = pair.splitn(2, '=');
+            (kv.next().unwrap(), kv.next().unwrap_or(""))
+        })
+        .collect();
+    pairs.sort();
+
+    options.token(token.token.identifier());
+
+    let mut signer = oauth::signer::Signer::form_with_signature_method(
+        signature_method,
+        method.as_str(),
+        uri,
+        token.client.secret(),
+        token.token.secret(),
+    );
+    let mut pairs = pairs.into_iter().peekable();
+    while let Some(&(k, v)) = pairs.peek() {
+        if k > "oauth_" {
+            break;
+        }
+        signer.parameter_encoded(k, v);
+        pairs.next();
+    }
+    let mut signer = signer.oauth_parameters(token.client.identifier(), &options);
+    for (k, v) in pairs {
+        signer.parameter_encoded(k, v);
+    }
+
+    signer.finish().authorization
+}
+
+/// Same as `oauth1_sign` with HMAC-SHA1, except that it also returns the signature base string.
+fn oauth1_signature_base<'a, C, A>(
+    token: &'a Token<C, A>,
+    method: &RequestMethod,
+    uri: &Uri,
+    params: &str,
+    options: oauth::Options<'a>,
+) -> SignatureBase
+where
+    C: Borrow<str>,
+    A: Borrow<str>,
+{
+    let mut base_string = String::new();
+    let authorization = oauth1_sign(
+        token,
+        method,
+        uri,
+        params,
+        options,
+        Recording(&mut base_string),
+    );
+    // The parameters are the third part of the base string, percent-encoded once more.
+    let normalized_parameters = base_string.splitn(3, '&').nth(2).unwrap_or("");
+    let normalized_parameters = percent_decode_str(normalized_parameters)
+        .decode_utf8()
+        .unwrap()
+        .into_owned();
+    SignatureBase {
+        base_string,
+        normalized_parameters,
+        authorization,
+    }
+}
+
+/// The HMAC-SHA1 signature method that also writes the signature base string to a `String`.
+struct Recording<'a>(&'a mut String);
+
+struct RecordingSign<'a> {
+    inner: <HmacSha1 as SignatureMethod>::Sign,
+    base_string: &'a mut String,
+}
+
+impl<'a> SignatureMethod for Recording<'a> {
+    type Sign = RecordingSign<'a>;
+
+    fn sign_with(
+        self,
+        consumer_secret: impl Display,
+        token_secret: Option<impl Display>,
+    ) -> RecordingSign<'a> {
+        RecordingSign {
+            inner: HmacSha1.sign_with(consumer_secret, token_secret),
+            base_string: self.0,
+        }
+    }
+}
+
+impl<'a> Sign for RecordingSign<'a> {
+    type Signature = <<HmacSha1 as SignatureMethod>::Sign as Sign>::Signature;
+
+    fn get_signature_method_name(&self) -> &'static str {
+        self.inner.get_signature_method_name()
+    }
+
+    fn request_method(&mut self, method: &str) {
+        self.inner.request_method(method);
+        self.base_string.push_str(method);
+        self.base_string.push('&');
+    }
+
+    fn uri(&mut self, uri: impl Display) {
+        write!(self.base_string, "{}&", uri).unwrap();
+        self.inner.uri(uri);
+    }
+
+    fn parameter(&mut self, key: &str, value: impl Display) {
+        write!(self.base_string, "{}%3D{}", key, value).unwrap();
+        self.inner.parameter(key, value);
+    }
+
+    fn delimiter(&mut self) {
+        self.inner.delimiter();
+        self.base_string.push_str("%26");
+    }
+
+    fn finish(self) -> Self::Signature {
+        self.inner.finish()
+    }
+}
+
+impl<T: Borrow<str>> Authorizer for BearerToken<T> {
+    fn authorize(&self, _: &RequestMethod, _: &Uri, _: &str) -> HeaderMap {
+        header(AUTHORIZATION, format!("Bearer {}", self.token.borrow()))
+    }
+}
+
+impl<U: Borrow<str>, P: Borrow<str>> Authorizer for BasicAuth<U, P> {
+    fn authorize(&self, _: &RequestMethod, _: &Uri, _: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let value = basic_auth(self.username.borrow(), self.password.borrow());
+        headers.insert(AUTHORIZATION, value);
+        headers
+    }
+}
+
+impl Authorizer for NoAuth {
+    fn authorize(&self, _: &RequestMethod, _: &Uri, _: &str) -> HeaderMap {
+        HeaderMap::new()
+    }
+}
+
+impl<F> Authorizer for FromFn<F>
+where
+    F: Fn(&RequestMethod, &Uri, &str) -> HeaderMap,
+{
+    fn authorize(&self, method: &RequestMethod, uri: &Uri, params: &str) -> HeaderMap {
+        (self.0)(method, uri, params)
+    }
+}
+
+fn header(name: HeaderName, value: String) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    // The OAuth 1.0a parameters are percent-encoded and a `BearerToken` is checked
+    // when it is constructed, so this never fails.
+    let mut value = HeaderValue::from_str(&value).expect("invalid header value");
+    // Keep the credentials out of the `Debug` output of the request.
+    value.set_sensitive(true);
+    headers.insert(name, value);
+    headers
+}
+
+impl<C: Borrow<str>, A: Borrow<str>> Debug for Deterministic<C, A> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Deterministic")
+            .field("token", &self.token)
+            .field("nonce", &self.nonce)
+            .field("timestamp", &self.timestamp)
+            .finish()
+    }
+}
+
+impl<U: Borrow<str>, P: Borrow<str>> Debug for BasicAuth<U, P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BasicAuth")
+            .field("username", &self.username.borrow())
+            .field("password", &Hidden)
+            .finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> Deserialize<'de> for BearerToken<T>
+where
+    T: Borrow<str> + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        BearerToken::try_new(T::deserialize(d)?).map_err(de::Error::custom)
+    }
+}
+
+impl<T> Debug for BearerToken<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BearerToken")
+            .field("token", &Hidden)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    use futures_executor::block_on;
+
+    use crate::util::{encode_params, respond, ServiceFn};
+    use crate::Builder;
+
+    #[test]
+    fn obtain() {
+        let credentials = Credentials::new(
+            "xvz1evFS4wEEPTGEFPHBog",
+            "L8qq9PZyRg6ieKGEKhZolGC0vJWLw8iEJ88DRdyOg",
+        );
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            assert_eq!(req.method(), RequestMethod::POST);
+            assert_eq!(req.uri(), "http://127.0.0.1/oauth2/token");
+            // Example from the Twitter Developer Documentation.
+            assert_eq!(
+                req.headers()[AUTHORIZATION],
+                "Basic eHZ6MWV2RlM0d0VFUFRHRUZQSEJvZzpMOHFxOVBaeVJnNmllS0dFS2hab2xHQzB2SldMdzhpRUo4OERSZHlPZw==",
+            );
+            assert_eq!(req.body(), b"grant_type=client_credentials");
+            respond(
+                StatusCode::OK,
+                r#"{"token_type":"bearer","access_token":"AAAA%2FAAA%3DAAAAAAAA"}"#,
+            )
+        });
+
+        let endpoint = Uri::from_static("http://127.0.0.1/oauth2/token");
+        let token = block_on(BearerToken::obtain_from(endpoint, credentials, client)).unwrap();
+        assert_eq!(token.token(), "AAAA%2FAAA%3DAAAAAAAA");
+    }
+
+    #[test]
+    fn obtain_error() {
+        let client = ServiceFn(|_: Request<Vec<u8>>| respond(StatusCode::FORBIDDEN, "{}"));
+        match block_on(BearerToken::obtain(Credentials::new("a", "b"), client)) {
+            Err(Error::Http(StatusCode::FORBIDDEN)) => {}
+            res => panic!("{:?}", res),
+        }
+
+        let client = ServiceFn(|_: Request<Vec<u8>>| {
+            respond(StatusCode::OK, r#"{"token_type":"mac","access_token":"a"}"#)
+        });
+        match block_on(BearerToken::obtain(Credentials::new("a", "b"), client)) {
+            Err(Error::Custom(_)) => {}
+            res => panic!("{:?}", res),
+        }
+    }
+
+    #[test]
+    fn bearer_request() {
+        let mut headers = None;
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            assert_eq!(req.body(), b"track=Rust");
+            headers = Some(req.headers().clone());
+            respond(StatusCode::OK, "")
+        });
+        block_on(
+            Builder::filter(BearerToken::new("AAAA"))
+                .track("Rust")
+                .listen_with_client(client),
+        )
+        .unwrap();
+
+        let headers: HeaderMap = headers.unwrap();
+        assert_eq!(headers[AUTHORIZATION], "Bearer AAAA");
+        assert_eq!(headers[CONTENT_LENGTH], "10");
+    }
+
+    #[test]
+    fn invalid_bearer_token() {
+        assert!(BearerToken::try_new("AAAA\n").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn bearer_token_from_env() {
+        assert!(json::from_str::<BearerToken>(r#""AAAA\n""#).is_err());
+
+        std::env::set_var("TWITTER_BEARER_TOKEN", "AAAA\n");
+        let token = BearerToken::from_env();
+        std::env::set_var("TWITTER_BEARER_TOKEN", "AAAA\nBBBB");
+        let invalid = BearerToken::from_env();
+        std::env::remove_var("TWITTER_BEARER_TOKEN");
+
+        assert_eq!(token.unwrap().token(), "AAAA");
+        match invalid {
+            Err(crate::config::Error::InvalidVar(name)) => assert_eq!(name, "TWITTER_BEARER_TOKEN"),
+            res => panic!("expected an invalid variable error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn oauth1_signature() {
+        let token = Token::new("ck", "cs", "ak", "as");
+        let uri = Uri::from_static("https://stream.twitter.com/1.1/statuses/filter.json");
+        let params: BTreeSet<_> = vec![("track", "Rust lang"), ("follow", "12,13"), ("count", "1")]
+            .into_iter()
+            .collect();
+
+        let mut options = oauth::Options::new();
+        options.nonce("nonce").timestamp(1);
+        let authorization = oauth1_sign(
+            &token,
+            &RequestMethod::POST,
+            &uri,
+            &encode_params(&params),
+            options,
+            HmacSha1,
+        );
+
+        let mut oauth = oauth::Builder::new(token.client.as_ref(), oauth::HmacSha1);
+        oauth
+            .token(token.token.as_ref())
+            .nonce("nonce")
+            .timestamp(1);
+        assert_eq!(authorization, oauth.post_form(&uri, &params).authorization);
+    }
+
+    #[test]
+    fn signature_base() {
+        // Example from the Twitter Developer Documentation.
+        let token = Token::new(
+            "xvz1evFS4wEEPTGEFPHBog",
+            "kAcSOqF21Fu85e7zjz7ZN2U4ZRhfV3WpwPAoE3Z7kBw",
+            "370773112-GmHxMAgYyLbNEtIKZeRNFsMKPR9EyMZeS9weJAEb",
+            "LswwdoUaIvS8ltyTt5jkRh4J50vUPVVHtR2YPi5kE",
+        );
+        let auth = Deterministic::new(
+            token,
+            "kYjzVBB8Y0ZFabxSWbWovY3uYSQ2pTgmZeNu2VS4cg",
+            1318622958,
+        );
+        let mut builder = Builder::custom(
+            RequestMethod::POST,
+            Uri::from_static("https://api.twitter.com/1.1/statuses/update.json"),
+            &auth,
+        );
+        builder.track("Hello Ladies + Gentlemen");
+
+        let base = builder.signature_base().unwrap();
+        assert_eq!(
+            base.base_string,
+            "POST&https%3A%2F%2Fapi.twitter.com%2F1.1%2Fstatuses%2Fupdate.json&\
+             oauth_consumer_key%3Dxvz1evFS4wEEPTGEFPHBog%26\
+             oauth_nonce%3DkYjzVBB8Y0ZFabxSWbWovY3uYSQ2pTgmZeNu2VS4cg%26\
+             oauth_signature_method%3DHMAC-SHA1%26\
+             oauth_timestamp%3D1318622958%26\
+             oauth_token%3D370773112-GmHxMAgYyLbNEtIKZeRNFsMKPR9EyMZeS9weJAEb%26\
+             track%3DHello%2520Ladies%2520%252B%2520Gentlemen",
+        );
+        assert_eq!(
+            base.normalized_parameters,
+            "oauth_consumer_key=xvz1evFS4wEEPTGEFPHBog&\
+             oauth_nonce=kYjzVBB8Y0ZFabxSWbWovY3uYSQ2pTgmZeNu2VS4cg&\
+             oauth_signature_method=HMAC-SHA1&\
+             oauth_timestamp=1318622958&\
+             oauth_token=370773112-GmHxMAgYyLbNEtIKZeRNFsMKPR9EyMZeS9weJAEb&\
+             track=Hello%20Ladies%20%2B%20Gentlemen",
+        );
+
+        // The same signature is sent with the request.
+        let mut authorization = None;
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            authorization = Some(req.headers()[AUTHORIZATION].clone());
+            respond(StatusCode::OK, "")
+        });
+        block_on(builder.listen_with_client(client)).unwrap();
+        assert_eq!(authorization.unwrap(), base.authorization);
+        assert_eq!(builder.signature_base().unwrap(), base);
+
+        // Signatures with a random nonce are consistent with the base string too.
+        let base = Builder::sample(auth.token.as_ref())
+            .signature_base()
+            .unwrap();
+        let nonce = base
+            .normalized_parameters
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("oauth_nonce="))
+            .unwrap();
+        assert!(base.authorization.contains(nonce));
+
+        assert!(Builder::sample(BearerToken::new("AAAA"))
+            .signature_base()
+            .is_none());
+    }
+
+    #[test]
+    fn redacted() {
+        let token = Token::new("ck", "consumer-secret", "ak", "access-secret");
+        let mut builder = Builder::filter(&token);
+        builder.track("Rust");
+        let debug = format!("{:?}", builder);
+        assert!(!debug.contains("secret"), "{}", debug);
+        let debug = format!("{:?}", token);
+        assert!(debug.contains("\"ck\""), "{}", debug);
+        assert!(!debug.contains("secret\""), "{}", debug);
+
+        let debug = format!("{:?}", BasicAuth::new("user", "password"));
+        assert!(!debug.contains("\"password\""), "{}", debug);
+        let debug = format!("{:?}", BearerToken::new("AAAA"));
+        assert!(!debug.contains("AAAA"), "{}", debug);
+
+        // The `Authorization` header of a request does not appear in its `Debug` output.
+        let debug = format!("{:?}", builder.build_request());
+        assert!(!debug.contains("oauth_signature"), "{}", debug);
+        let debug = format!(
+            "{:?}",
+            Builder::sample(BearerToken::new("AAAA")).build_request()
+        );
+        assert!(!debug.contains("AAAA"), "{}", debug);
+    }
+
+    #[test]
+    fn other_authorizers() {
+        fn headers<A: Authorizer>(authorizer: A) -> HeaderMap {
+            let mut headers = None;
+            let client = ServiceFn(|req: Request<Vec<u8>>| {
+                assert_eq!(
+                    req.uri(),
+                    "https://stream.twitter.com/1.1/statuses/sample.json"
+                );
+                headers = Some(req.headers().clone());
+                respond(StatusCode::OK, "")
+            });
+            block_on(Builder::sample(authorizer).listen_with_client(client)).unwrap();
+            headers.unwrap()
+        }
+
+        let basic = headers(BasicAuth::new("user", "pass"));
+        assert_eq!(basic[AUTHORIZATION], "Basic dXNlcjpwYXNz");
+
+        let none = headers(NoAuth);
+        assert!(!none.contains_key(AUTHORIZATION));
+
+        let custom = headers(from_fn(
+            |method: &RequestMethod, uri: &Uri, params: &str| {
+                assert_eq!(method, RequestMethod::GET);
+                assert_eq!(uri, "https://stream.twitter.com/1.1/statuses/sample.json");
+                assert_eq!(params, "");
+                let mut headers = HeaderMap::new();
+                headers.insert("x-gateway-key", HeaderValue::from_static("secret"));
+                headers
+            },
+        ));
+        assert_eq!(custom["x-gateway-key"], "secret");
+        assert!(!custom.contains_key(AUTHORIZATION));
+    }
+}
@@ -0,0 +1,488 @@
+use std::cmp;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use http::header::HeaderMap;
+
+use super::{Authorizer, ConnectionEvent, SignatureBase};
+use crate::error::Error;
+use crate::types::{RequestMethod, StatusCode, Uri};
+use crate::v2::Problem;
+
+/// The cool-down period after the first rate limiting of a token. It doubles on each
+/// consecutive rate limiting, as Twitter recommends.
+const MIN_COOL_DOWN: Duration = Duration::from_secs(60);
+
+/// The upper bound of the cool-down period, which is the length of a rate limit window.
+const MAX_COOL_DOWN: Duration = Duration::from_secs(15 * 60);
+
+/// The `code` of the `disconnect` message sent when the token has been revoked.
+const TOKEN_REVOKED: u64 = 6;
+
+/// A pool of tokens which rotates to another token when one is rate limited or revoked.
+///
+/// The pool is an `Authorizer` that signs the request of each connection with the first usable
+/// token, starting from the one used for the previous connection. The token is leased to
+/// the connection (see `TokenLease`), which is reported the outcome of the connection by
+/// `FutureTwitterStream` and `TwitterStream`:
+///
+/// - A rate limited token (HTTP 420 or 429) cools down for a minute, doubling on each
+///   consecutive rate limiting up to 15 minutes.
+/// - A rejected token (HTTP 401) or a revoked one (a `disconnect` message with code 6)
+///   is marked as dead.
+///
+/// The pool is cheaply cloneable and the clones share the state, so a clone (or a reference)
+/// can be given to each `Builder`.
+///
+/// ```
+/// use twitter_stream::auth::TokenPool;
+/// use twitter_stream::types::StatusCode;
+/// use twitter_stream::{Builder, Error, Token};
+///
+/// let pool = TokenPool::new(vec![
+///     Token::new("ck1", "cs1", "ak1", "as1"),
+///     Token::new("ck2", "cs2", "ak2", "as2"),
+/// ]);
+/// let builder = Builder::sample(pool.clone());
+///
+/// // When a request is sent in some other way than with `listen`,
+/// // the outcome has to be reported to the lease by hand:
+/// let lease = pool.lease();
+/// let request = Builder::sample(&lease).build_request();
+/// lease.report_error(&Error::<()>::Http(StatusCode::TOO_MANY_REQUESTS));
+/// assert_eq!(pool.health().cooling_down, 1);
+/// // ... so the next connection uses the second token.
+/// ```
+pub struct TokenPool<T> {
+    tokens: Arc<[T]>,
+    inner: Arc<Mutex<Inner>>,
+}
+
+/// A token of a `TokenPool` chosen for a single connection.
+///
+/// This is an `Authorizer` that signs with the chosen token, and the outcome of
+/// the connection is reported to it to update the state of the token in the pool.
+pub struct TokenLease<T> {
+    tokens: Arc<[T]>,
+    inner: Arc<Mutex<Inner>>,
+    index: usize,
+}
+
+/// The state of a token in a `TokenPool`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenStatus {
+    /// The token can be used.
+    Available,
+    /// The token has been rate limited and will be available again at the given time.
+    CoolingDown(Instant),
+    /// The token has been revoked or rejected and will not be used again.
+    Dead,
+}
+
+/// A summary of the states of the tokens in a `TokenPool`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolHealth {
+    /// The number of tokens that can be used.
+    pub available: usize,
+    /// The number of tokens that have been rate limited and are cooling down.
+    pub cooling_down: usize,
+    /// The number of tokens that have been revoked or rejected.
+    pub dead: usize,
+}
+
+// The tokens are kept out of the mutex, so that it is not held while signing a request.
+struct Inner {
+    entries: Vec<Entry>,
+    current: usize,
+}
+
+struct Entry {
+    status: TokenStatus,
+    rate_limited: u32,
+}
+
+impl<T> TokenPool<T> {
+    /// Create a pool with the given tokens, which are used in order.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `tokens` is empty.
+    pub fn new(tokens: impl IntoIterator<Item = T>) -> Self {
+        let tokens: Arc<[T]> = tokens.into_iter().collect::<Vec<_>>().into();
+        assert!(!tokens.is_empty(), "a token pool must not be empty");
+        let entries = tokens
+            .iter()
+            .map(|_| Entry {
+                status: TokenStatus::Available,
+                rate_limited: 0,
+            })
+            .collect();
+        TokenPool {
+            tokens,
+            inner: Arc::new(Mutex::new(Inner {
+                entries,
+                current: 0,
+            })),
+        }
+    }
+
+    /// Choose the token to be used for a new connection.
+    ///
+    /// If all the tokens are cooling down, the one that will be available first is chosen.
+    /// If all the tokens are dead, the token of the previous connection is chosen again.
+    pub fn lease(&self) -> TokenLease<T> {
+        let index = lock(&self.inner).select(Instant::now());
+        TokenLease {
+            tokens: self.tokens.clone(),
+            inner: self.inner.clone(),
+            index,
+        }
+    }
+
+    /// Returns the state of each token, in the order they were given to `new`.
+    pub fn status(&self) -> Vec<TokenStatus> {
+        let now = Instant::now();
+        let inner = lock(&self.inner);
+        inner.entries.iter().map(|e| e.status_at(now)).collect()
+    }
+
+    /// Returns the number of tokens in each state.
+    pub fn health(&self) -> PoolHealth {
+        let mut health = PoolHealth::default();
+        for status in self.status() {
+            match status {
+                TokenStatus::Available => health.available += 1,
+                TokenStatus::CoolingDown(_) => health.cooling_down += 1,
+                TokenStatus::Dead => health.dead += 1,
+            }
+        }
+        health
+    }
+}
+
+impl<T> TokenLease<T> {
+    /// Update the state of the token according to the error of the connection.
+    ///
+    /// Errors other than rate limiting (HTTP 420 or 429) and rejection (HTTP 401)
+    /// do not affect the pool.
+    pub fn report_error<E>(&self, error: &Error<E>) {
+        match *error {
+            Error::Http(status) | Error::Api(Problem { status, .. }) => self.report_status(status),
+            _ => {}
+        }
+    }
+
+    /// Update the state of the token according to the `code` of a `disconnect` message.
+    ///
+    /// The token is marked as dead if it has been revoked (code 6).
+    pub fn report_disconnect(&self, code: u64) {
+        if code == TOKEN_REVOKED {
+            self.with_entry(|entry| entry.status = TokenStatus::Dead);
+        }
+    }
+
+    /// Reset the rate limiting count of the token after a successful connection.
+    pub fn report_connected(&self) {
+        self.with_entry(|entry| entry.rate_limited = 0);
+    }
+
+    fn report_status(&self, status: StatusCode) {
+        let now = Instant::now();
+        self.with_entry(|entry| match status.as_u16() {
+            420 | 429 => {
+                entry.rate_limited = entry.rate_limited.saturating_add(1);
+                let cool_down = MIN_COOL_DOWN
+                    .checked_mul(1 << cmp::min(entry.rate_limited - 1, 4))
+                    .map_or(MAX_COOL_DOWN, |d| cmp::min(d, MAX_COOL_DOWN));
+                entry.status = TokenStatus::CoolingDown(now + cool_down);
+            }
+            401 => entry.status = TokenStatus::Dead,
+            _ => {}
+        });
+    }
+
+    fn with_entry<R>(&self, f: impl FnOnce(&mut Entry) -> R) -> R {
+        f(&mut lock(&self.inner).entries[self.index])
+    }
+
+    fn token(&self) -> &T {
+        &self.tokens[self.index]
+    }
+}
+
+fn lock(inner: &Mutex<Inner>) -> MutexGuard<'_, Inner> {
+    // The state is always consistent, even if another thread panicked.
+    inner.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Inner {
+    /// Select the first available token, starting from the current one,
+    /// and return its index.
+    fn select(&mut self, now: Instant) -> usize {
+        let len = self.entries.len();
+        let mut soonest: Option<(usize, Instant)> = None;
+        for i in (0..len).map(|i| (self.current + i) % len) {
+            match self.entries[i].status_at(now) {
+                TokenStatus::Available => {
+                    self.current = i;
+                    return i;
+                }
+                TokenStatus::CoolingDown(until) => match soonest {
+                    Some((_, t)) if t <= until => {}
+                    _ => soonest = Some((i, until)),
+                },
+                TokenStatus::Dead => {}
+            }
+        }
+        if let Some((i, _)) = soonest {
+            self.current = i;
+        }
+        self.current
+    }
+}
+
+impl Entry {
+    fn status_at(&self, now: Instant) -> TokenStatus {
+        match self.status {
+            TokenStatus::CoolingDown(until) if until <= now => TokenStatus::Available,
+            status => status,
+        }
+    }
+}
+
+impl<T> Clone for TokenPool<T> {
+    fn clone(&self) -> Self {
+        TokenPool {
+            tokens: self.tokens.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Debug for TokenPool<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // The tokens are left out because they may contain secrets.
+        f.debug_struct("TokenPool")
+            .field("status", &self.status())
+            .field("current", &lock(&self.inner).current)
+            .finish()
+    }
+}
+
+impl<T> Debug for TokenLease<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenLease")
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+impl<T> Authorizer for TokenPool<T>
+where
+    T: Authorizer + Send + Sync + 'static,
+{
+    fn authorize(&self, method: &RequestMethod, uri: &Uri, params: &str) -> HeaderMap {
+        self.lease().authorize(method, uri, params)
+    }
+
+    fn authorize_with_clock_offset(
+        &self,
+        method: &RequestMethod,
+        uri: &Uri,
+        params: &str,
+        clock_offset: i64,
+    ) -> HeaderMap {
+        self.lease()
+            .authorize_with_clock_offset(method, uri, params, clock_offset)
+    }
+
+    fn signature_base(
+        &self,
+        method: &RequestMethod,
+        uri: &Uri,
+        params: &str,
+        clock_offset: i64,
+    ) -> Option<SignatureBase> {
+        self.lease()
+            .signature_base(method, uri, params, clock_offset)
+    }
+
+    fn for_connection(&self) -> Option<Box<dyn Authorizer + Send + Sync>> {
+        Some(Box::new(self.lease()))
+    }
+}
+
+impl<T: Authorizer> Authorizer for TokenLease<T> {
+    fn authorize(&self, method: &RequestMethod, uri: &Uri, params: &str) -> HeaderMap {
+        self.token().authorize(method, uri, params)
+    }
+
+    fn authorize_with_clock_offset(
+        &self,
+        method: &RequestMethod,
+        uri: &Uri,
+        params: &str,
+        clock_offset: i64,
+    ) -> HeaderMap {
+        self.token()
+            .authorize_with_clock_offset(method, uri, params, clock_offset)
+    }
+
+    fn clock_dependent(&self) -> Option<Box<dyn Authorizer + Send + Sync>> {
+        self.token().clock_dependent()
+    }
+
+    fn signature_base(
+        &self,
+        method: &RequestMethod,
+        uri: &Uri,
+        params: &str,
+        clock_offset: i64,
+    ) -> Option<SignatureBase> {
+        self.token()
+            .signature_base(method, uri, params, clock_offset)
+    }
+
+    fn report(&self, event: ConnectionEvent) {
+        match event {
+            ConnectionEvent::Connected => self.report_connected(),
+            ConnectionEvent::Rejected(status) => self.report_status(status),
+            ConnectionEvent::Disconnected(code) => self.report_disconnect(code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures_executor::block_on;
+    use futures_util::TryStreamExt;
+    use http::header::AUTHORIZATION;
+    use http::Request;
+
+    use crate::auth::BearerToken;
+    use crate::util::{respond, ServiceFn};
+    use crate::Builder;
+
+    /// Connect with `pool`, responding with `status` and `body`, and read the whole stream.
+    /// Returns the `Authorization` header of the request.
+    fn connect(
+        pool: &TokenPool<BearerToken<&'static str>>,
+        status: u16,
+        body: &'static str,
+    ) -> String {
+        let mut authorization = None;
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            authorization = Some(req.headers()[AUTHORIZATION].to_str().unwrap().to_owned());
+            respond(StatusCode::from_u16(status).unwrap(), body)
+        });
+        let result = block_on(async {
+            let stream = Builder::sample(pool).listen_with_client(client).await?;
+            stream.try_collect::<Vec<_>>().await
+        });
+        assert_eq!(result.is_ok(), status == 200);
+        authorization.unwrap()
+    }
+
+    fn pool() -> TokenPool<BearerToken<&'static str>> {
+        TokenPool::new(vec![
+            BearerToken::new("A"),
+            BearerToken::new("B"),
+            BearerToken::new("C"),
+        ])
+    }
+
+    #[test]
+    fn rotate() {
+        let pool = pool();
+        assert_eq!(connect(&pool, 200, ""), "Bearer A");
+        assert_eq!(connect(&pool, 420, ""), "Bearer A");
+        assert_eq!(
+            connect(&pool, 200, "{\"disconnect\":{\"code\":6}}\r\n"),
+            "Bearer B"
+        );
+        assert_eq!(connect(&pool, 200, ""), "Bearer C");
+
+        // Errors not caused by the token are ignored.
+        assert_eq!(connect(&pool, 503, ""), "Bearer C");
+        assert_eq!(
+            connect(&pool, 200, "{\"disconnect\":{\"code\":4}}\r\n"),
+            "Bearer C"
+        );
+
+        assert_eq!(connect(&pool, 401, ""), "Bearer C");
+        assert_eq!(
+            pool.health(),
+            PoolHealth {
+                available: 0,
+                cooling_down: 1,
+                dead: 2,
+            }
+        );
+        let status = pool.status();
+        assert!(matches!(status[0], TokenStatus::CoolingDown(_)));
+        assert_eq!(status[1..], [TokenStatus::Dead, TokenStatus::Dead]);
+
+        // Falls back to the token that is cooling down.
+        assert_eq!(connect(&pool, 200, ""), "Bearer A");
+    }
+
+    #[test]
+    fn lease() {
+        let pool = pool();
+        let a = pool.lease();
+        // Leasing alone does not rotate.
+        let also_a = pool.lease();
+        a.report_error(&Error::<()>::Http(StatusCode::TOO_MANY_REQUESTS));
+        let b = pool.lease();
+
+        // A report concerns the leased token even after the pool has moved on.
+        also_a.report_error(&Error::<()>::Http(StatusCode::UNAUTHORIZED));
+        assert_eq!(pool.status()[0], TokenStatus::Dead);
+        assert_eq!(pool.status()[1], TokenStatus::Available);
+
+        b.report_disconnect(TOKEN_REVOKED);
+        assert_eq!(pool.status()[1], TokenStatus::Dead);
+        assert_eq!(
+            Builder::sample(&pool.lease()).build_request().headers()[AUTHORIZATION],
+            "Bearer C"
+        );
+    }
+
+    #[test]
+    fn cool_down() {
+        let pool = pool();
+        let rate_limited = Error::<()>::Http(StatusCode::TOO_MANY_REQUESTS);
+        let until = |pool: &TokenPool<_>| match pool.status()[0] {
+            TokenStatus::CoolingDown(until) => until.duration_since(Instant::now()),
+            status => panic!("expected a cool-down, got {:?}", status),
+        };
+        let lease = pool.lease();
+
+        lease.report_error(&rate_limited);
+        assert!(until(&pool) <= MIN_COOL_DOWN);
+        assert!(until(&pool) > MIN_COOL_DOWN / 2);
+
+        lease.report_error(&rate_limited);
+        assert!(until(&pool) > MIN_COOL_DOWN);
+        assert!(until(&pool) <= MIN_COOL_DOWN * 2);
+
+        lease.report_connected();
+        lease.report_error(&rate_limited);
+        assert!(until(&pool) <= MIN_COOL_DOWN);
+
+        for _ in 0..10 {
+            lease.report_error(&rate_limited);
+        }
+        assert!(until(&pool) <= MAX_COOL_DOWN);
+        assert!(until(&pool) > MAX_COOL_DOWN / 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "a token pool must not be empty")]
+    fn empty() {
+        TokenPool::<BearerToken>::new(Vec::new());
+    }
+}
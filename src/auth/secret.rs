@@ -0,0 +1,100 @@
+use std::borrow::Borrow;
+use std::fmt::{self, Debug, Formatter};
+
+#[cfg(feature = "serde")]
+use serde::de::{Deserialize, Deserializer};
+use zeroize::Zeroize;
+
+use crate::util::Hidden;
+
+/// A string that is overwritten with zeroes when dropped, for holding credentials.
+///
+/// This implements `Borrow<str>` so it can be used in place of `String` in the credentials
+/// types, e.g. `Token<Secret, Secret>` or `BearerToken<Secret>`. Unlike `String`,
+/// its `Debug` output does not show the contents.
+///
+/// This requires the `zeroize` feature.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Secret(secret.into())
+    }
+
+    /// Borrow the contents of the secret.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Secret {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(secret: String) -> Self {
+        Secret(secret)
+    }
+}
+
+impl<'a> From<&'a str> for Secret {
+    fn from(secret: &'a str) -> Self {
+        Secret(secret.to_owned())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d).map(Secret)
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Secret").field(&Hidden).finish()
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::auth::BearerToken;
+    use crate::Token;
+
+    #[test]
+    fn redacted() {
+        let secret = Secret::new("AAAA");
+        assert_eq!(format!("{:?}", secret), "Secret(<hidden>)");
+        assert_eq!(secret.expose(), "AAAA");
+        assert!(!format!("{:?}", BearerToken::new(secret)).contains("AAAA"));
+
+        let token = Token::new(
+            Secret::new("ck"),
+            Secret::new("cs"),
+            Secret::new("ak"),
+            Secret::new("as"),
+        );
+        assert_eq!(token.as_ref().token.secret(), "as");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize() {
+        let token: Token<Secret, Secret> = json::from_str(
+            r#"{"consumer_key":"ck","consumer_secret":"cs","access_key":"ak","access_secret":"as"}"#,
+        )
+        .unwrap();
+        assert_eq!(token.client.secret(), "cs");
+    }
+}
@@ -0,0 +1,525 @@
+//! Helpers for the three-legged OAuth 1.0a flow, to obtain a `Token` for a user.
+//!
+//! 1. Obtain a request token with `request_token`.
+//! 2. Send the user to `authorize_url(request_token.identifier())`.
+//! 3. Receive the `Callback` at the callback URL (with `listen_callback` for a loopback URL),
+//!    or have the user enter the PIN if the callback is `oob`.
+//! 4. Exchange the verifier for an access token with `access_token`.
+//!
+//! See the [Twitter Developer Documentation][1] for more information.
+//!
+//! [1]: https://developer.twitter.com/en/docs/authentication/oauth-1-0a/obtaining-user-access-tokens
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use twitter_stream::auth::sign_in::{self, Callback};
+//! use twitter_stream::Credentials;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let conn = hyper_tls::HttpsConnector::new();
+//! # let client = hyper_pkg::Client::builder().build::<_, hyper_pkg::Body>(conn);
+//! let consumer = Credentials::new("consumer_key", "consumer_secret");
+//!
+//! let listener = sign_in::listen_callback(&([127, 0, 0, 1], 8080).into()).unwrap();
+//! let callback = format!("http://{}/callback", listener.local_addr());
+//!
+//! let request_token = sign_in::request_token(consumer, &callback, client.clone()).await.unwrap();
+//! println!("Open {}", sign_in::authorize_url(request_token.identifier()));
+//!
+//! match listener.await.unwrap() {
+//!     Callback::Approved { verifier, .. } => {
+//!         let res = sign_in::access_token(consumer, request_token.as_ref(), &verifier, client)
+//!             .await
+//!             .unwrap();
+//!         println!("Signed in as @{}", res.screen_name);
+//!     }
+//!     Callback::Denied { .. } => println!("Denied"),
+//! }
+//! # }
+//! ```
+
+use std::borrow::{Borrow, Cow};
+use std::future::Future;
+
+use http::header::{HeaderValue, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response};
+use http_body::Body;
+use percent_encoding::{percent_decode, utf8_percent_encode, NON_ALPHANUMERIC};
+use tower_service::Service;
+
+use crate::error::Error;
+use crate::types::StatusCode;
+use crate::util::read_body;
+use crate::{Credentials, Token};
+
+const REQUEST_TOKEN_URI: &str = "https://api.twitter.com/oauth/request_token";
+const AUTHORIZE_URI: &str = "https://api.twitter.com/oauth/authorize";
+const ACCESS_TOKEN_URI: &str = "https://api.twitter.com/oauth/access_token";
+
+/// The response of `access_token`.
+#[derive(Clone, Debug)]
+pub struct AccessToken {
+    /// The token to make requests on behalf of the user.
+    pub token: Token,
+    pub user_id: u64,
+    pub screen_name: String,
+}
+
+/// The parameters of a request to the callback URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Callback {
+    /// The user authorized the app.
+    Approved {
+        /// The identifier of the request token.
+        token: String,
+        /// The `oauth_verifier` to be passed to `access_token`.
+        verifier: String,
+    },
+    /// The user declined to authorize the app.
+    Denied {
+        /// The identifier of the request token.
+        token: String,
+    },
+}
+
+impl Callback {
+    /// Parse the query string of a request to the callback URL.
+    ///
+    /// Returns `None` if the query does not contain the callback parameters.
+    pub fn from_query(query: &str) -> Option<Self> {
+        let mut token = None;
+        let mut verifier = None;
+        let mut denied = None;
+        for (k, v) in parse_form(query.as_bytes()) {
+            match &*k {
+                "oauth_token" => token = Some(v.into_owned()),
+                "oauth_verifier" => verifier = Some(v.into_owned()),
+                "denied" => denied = Some(v.into_owned()),
+                _ => {}
+            }
+        }
+
+        match (token, verifier, denied) {
+            (Some(token), Some(verifier), _) => Some(Callback::Approved { token, verifier }),
+            (_, _, Some(token)) => Some(Callback::Denied { token }),
+            _ => None,
+        }
+    }
+}
+
+/// Obtain a request token at the `POST oauth/request_token` endpoint,
+/// using `client` to make the HTTP request.
+///
+/// `callback` is the URL to which the user is redirected after the authorization,
+/// or `oob` for the PIN-based flow.
+///
+/// # Panics
+///
+/// This will call `<S as Service>::call` without checking for `<S as Service>::poll_ready`
+/// and may cause a panic if `client` is not ready to send an HTTP request yet.
+pub fn request_token<C, S, ReqB, ResB>(
+    client_credentials: Credentials<C>,
+    callback: &str,
+    client: S,
+) -> impl Future<Output = Result<Credentials, Error<S::Error>>>
+where
+    C: Borrow<str>,
+    S: Service<Request<ReqB>, Response = Response<ResB>>,
+    ReqB: From<Vec<u8>>,
+    ResB: Body<Error = S::Error>,
+{
+    let mut builder = oauth::Builder::new(client_credentials.as_ref(), oauth::HmacSha1);
+    builder.callback(callback);
+    let authorization = builder.post_form(REQUEST_TOKEN_URI, ()).authorization;
+    let response = post(REQUEST_TOKEN_URI, authorization, client);
+
+    async move {
+        let mut token = None;
+        let mut secret = None;
+        let mut confirmed = false;
+        for (k, v) in parse_form(&response.await?) {
+            match &*k {
+                "oauth_token" => token = Some(v.into_owned()),
+                "oauth_token_secret" => secret = Some(v.into_owned()),
+                "oauth_callback_confirmed" => confirmed = v == "true",
+                _ => {}
+            }
+        }
+
+        if !confirmed {
+            return Err(Error::custom("the callback was not confirmed"));
+        }
+        match (token, secret) {
+            (Some(token), Some(secret)) => Ok(Credentials::new(token, secret)),
+            _ => Err(Error::custom("missing request token in the response")),
+        }
+    }
+}
+
+/// Returns the URL of the `GET oauth/authorize` page, to which the user should be sent
+/// to authorize the app.
+pub fn authorize_url(request_token: &str) -> String {
+    format!(
+        "{}?oauth_token={}",
+        AUTHORIZE_URI,
+        utf8_percent_encode(request_token, NON_ALPHANUMERIC)
+    )
+}
+
+/// Exchange a request token and the `oauth_verifier` (or the PIN) for an access token
+/// at the `POST oauth/access_token` endpoint, using `client` to make the HTTP request.
+///
+/// # Panics
+///
+/// This will call `<S as Service>::call` without checking for `<S as Service>::poll_ready`
+/// and may cause a panic if `client` is not ready to send an HTTP request yet.
+pub fn access_token<C, T, S, ReqB, ResB>(
+    client_credentials: Credentials<C>,
+    request_token: Credentials<T>,
+    verifier: &str,
+    client: S,
+) -> impl Future<Output = Result<AccessToken, Error<S::Error>>>
+where
+    C: Borrow<str>,
+    T: Borrow<str>,
+    S: Service<Request<ReqB>, Response = Response<ResB>>,
+    ReqB: From<Vec<u8>>,
+    ResB: Body<Error = S::Error>,
+{
+    let consumer_key = client_credentials.identifier().to_owned();
+    let consumer_secret = client_credentials.secret().to_owned();
+
+    let mut builder = oauth::Builder::new(client_credentials.as_ref(), oauth::HmacSha1);
+    builder.token(request_token.as_ref()).verifier(verifier);
+    let authorization = builder.post_form(ACCESS_TOKEN_URI, ()).authorization;
+    let response = post(ACCESS_TOKEN_URI, authorization, client);
+
+    async move {
+        let mut token = None;
+        let mut secret = None;
+        let mut user_id = None;
+        let mut screen_name = None;
+        for (k, v) in parse_form(&response.await?) {
+            match &*k {
+                "oauth_token" => token = Some(v.into_owned()),
+                "oauth_token_secret" => secret = Some(v.into_owned()),
+                "user_id" => user_id = v.parse().ok(),
+                "screen_name" => screen_name = Some(v.into_owned()),
+                _ => {}
+            }
+        }
+
+        match (token, secret, user_id, screen_name) {
+            (Some(token), Some(secret), Some(user_id), Some(screen_name)) => Ok(AccessToken {
+                token: Token::new(consumer_key, consumer_secret, token, secret),
+                user_id,
+                screen_name,
+            }),
+            _ => Err(Error::custom("missing access token in the response")),
+        }
+    }
+}
+
+/// Send a signed `POST` request with an empty body and read the response body.
+fn post<S, ReqB, ResB>(
+    uri: &'static str,
+    authorization: String,
+    mut client: S,
+) -> impl Future<Output = Result<Vec<u8>, Error<S::Error>>>
+where
+    S: Service<Request<ReqB>, Response = Response<ResB>>,
+    ReqB: From<Vec<u8>>,
+    ResB: Body<Error = S::Error>,
+{
+    let mut authorization = HeaderValue::from_str(&authorization).unwrap();
+    authorization.set_sensitive(true);
+    let req = Request::post(uri)
+        .header(AUTHORIZATION, authorization)
+        .header(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        )
+        .header(CONTENT_LENGTH, 0)
+        .body(Vec::new().into())
+        .unwrap();
+    let response = client.call(req);
+
+    async move {
+        let res = response.await.map_err(Error::Service)?;
+        if StatusCode::OK != res.status() {
+            return Err(Error::Http(res.status()));
+        }
+        read_body(res.into_body()).await
+    }
+}
+
+fn parse_form(form: &[u8]) -> impl Iterator<Item = (Cow<'_, str>, Cow<'_, str>)> {
+    form.split(|&b| b == b'&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut kv = pair.splitn(2, |&b| b == b'=');
+            let k = percent_decode(kv.next().unwrap()).decode_utf8_lossy();
+            let v = percent_decode(kv.next().unwrap_or(b"")).decode_utf8_lossy();
+            (k, v)
+        })
+}
+
+#[cfg(feature = "hyper")]
+pub use self::listener::{listen_callback, CallbackListener};
+
+#[cfg(feature = "hyper")]
+mod listener {
+    use std::future::Future;
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+
+    use futures_channel::oneshot;
+    use futures_util::future::{self, Either};
+    use hyper_pkg::service::{make_service_fn, service_fn};
+    use hyper_pkg::{Body, Response, Server};
+
+    use super::Callback;
+    use crate::error::Error;
+    use crate::types::StatusCode;
+
+    const RESPONSE: &str = "You can close this window and return to the application.";
+
+    /// A `Future` that resolves to the first `Callback` received by a local HTTP server.
+    pub struct CallbackListener {
+        local_addr: SocketAddr,
+        inner: Pin<Box<dyn Future<Output = Result<Callback, Error<hyper_pkg::Error>>> + Send>>,
+    }
+
+    /// Start a local HTTP server on `addr` to receive the redirect to a loopback callback URL
+    /// (e.g. `http://127.0.0.1:8080/callback`).
+    ///
+    /// The server stops after receiving a request with the callback parameters.
+    pub fn listen_callback(addr: &SocketAddr) -> Result<CallbackListener, hyper_pkg::Error> {
+        let (tx, rx) = oneshot::channel();
+        let tx = Arc::new(Mutex::new(Some(tx)));
+        let make_service = make_service_fn(move |_| {
+            let tx = tx.clone();
+            future::ok::<_, hyper_pkg::Error>(service_fn(move |req| {
+                let callback = req.uri().query().and_then(Callback::from_query);
+                let res = if let Some(callback) = callback {
+                    if let Some(tx) = tx.lock().unwrap().take() {
+                        let _ = tx.send(callback);
+                    }
+                    Response::new(Body::from(RESPONSE))
+                } else {
+                    let mut res = Response::new(Body::empty());
+                    *res.status_mut() = StatusCode::NOT_FOUND;
+                    res
+                };
+                future::ok::<_, hyper_pkg::Error>(res)
+            }))
+        });
+
+        let server = Server::try_bind(addr)?.serve(make_service);
+        let local_addr = server.local_addr();
+        let inner = async move {
+            match future::select(server, rx).await {
+                Either::Left((result, _)) => {
+                    result.map_err(Error::Service)?;
+                    Err(Error::custom("the server stopped unexpectedly"))
+                }
+                Either::Right((callback, _)) => Ok(callback.unwrap()),
+            }
+        };
+
+        Ok(CallbackListener {
+            local_addr,
+            inner: Box::pin(inner),
+        })
+    }
+
+    impl CallbackListener {
+        /// Returns the address that the server is bound to.
+        pub fn local_addr(&self) -> SocketAddr {
+            self.local_addr
+        }
+    }
+
+    impl Future for CallbackListener {
+        type Output = Result<Callback, Error<hyper_pkg::Error>>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            self.inner.as_mut().poll(cx)
+        }
+    }
+
+    impl std::fmt::Debug for CallbackListener {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("CallbackListener")
+                .field("local_addr", &self.local_addr)
+                .finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures_executor::block_on;
+
+    use crate::types::RequestMethod;
+    use crate::util::{respond, ServiceFn};
+
+    fn oauth_params(req: &Request<Vec<u8>>) -> Vec<(String, String)> {
+        let header = req.headers()[AUTHORIZATION].to_str().unwrap();
+        let mut params: Vec<_> = header
+            .trim_start_matches("OAuth ")
+            .split(',')
+            .map(|pair| {
+                let mut kv = pair.splitn(2, '=');
+                let k = kv.next().unwrap().to_owned();
+                let v = kv.next().unwrap().trim_matches('"');
+                (
+                    k,
+                    percent_decode(v.as_bytes())
+                        .decode_utf8_lossy()
+                        .into_owned(),
+                )
+            })
+            .filter(|(k, _)| !["oauth_nonce", "oauth_signature", "oauth_timestamp"].contains(&&**k))
+            .collect();
+        params.sort();
+        params
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn flow() {
+        let consumer = Credentials::new("ck", "cs");
+
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            assert_eq!(req.method(), RequestMethod::POST);
+            assert_eq!(req.uri(), REQUEST_TOKEN_URI);
+            assert_eq!(
+                oauth_params(&req),
+                pairs(&[
+                    ("oauth_callback", "http://127.0.0.1:8080/callback"),
+                    ("oauth_consumer_key", "ck"),
+                    ("oauth_signature_method", "HMAC-SHA1"),
+                ]),
+            );
+            respond(
+                StatusCode::OK,
+                "oauth_token=rt&oauth_token_secret=rs&oauth_callback_confirmed=true",
+            )
+        });
+        let request_token = block_on(request_token(
+            consumer,
+            "http://127.0.0.1:8080/callback",
+            client,
+        ))
+        .unwrap();
+        assert_eq!(request_token.identifier(), "rt");
+        assert_eq!(request_token.secret(), "rs");
+
+        assert_eq!(
+            authorize_url(request_token.identifier()),
+            "https://api.twitter.com/oauth/authorize?oauth_token=rt"
+        );
+
+        let callback = Callback::from_query("oauth_token=rt&oauth_verifier=v%2B1").unwrap();
+        assert_eq!(
+            callback,
+            Callback::Approved {
+                token: "rt".into(),
+                verifier: "v+1".into(),
+            }
+        );
+
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            assert_eq!(req.uri(), ACCESS_TOKEN_URI);
+            assert_eq!(
+                oauth_params(&req),
+                pairs(&[
+                    ("oauth_consumer_key", "ck"),
+                    ("oauth_signature_method", "HMAC-SHA1"),
+                    ("oauth_token", "rt"),
+                    ("oauth_verifier", "v+1"),
+                ]),
+            );
+            respond(
+                StatusCode::OK,
+                "oauth_token=1-at&oauth_token_secret=as&user_id=1&screen_name=bot",
+            )
+        });
+        let res = block_on(access_token(
+            consumer,
+            request_token.as_ref(),
+            "v+1",
+            client,
+        ))
+        .unwrap();
+        assert_eq!(res.user_id, 1);
+        assert_eq!(res.screen_name, "bot");
+        assert_eq!(res.token.client.identifier(), "ck");
+        assert_eq!(res.token.token.identifier(), "1-at");
+        assert_eq!(res.token.token.secret(), "as");
+    }
+
+    #[test]
+    fn errors() {
+        let consumer = Credentials::new("ck", "cs");
+
+        let client = ServiceFn(|_: Request<Vec<u8>>| {
+            respond(StatusCode::OK, "oauth_token=rt&oauth_token_secret=rs")
+        });
+        match block_on(request_token(consumer, "oob", client)) {
+            Err(Error::Custom(_)) => {}
+            res => panic!("{:?}", res),
+        }
+
+        let client = ServiceFn(|_: Request<Vec<u8>>| respond(StatusCode::UNAUTHORIZED, ""));
+        match block_on(access_token(consumer, consumer, "pin", client)) {
+            Err(Error::Http(StatusCode::UNAUTHORIZED)) => {}
+            res => panic!("{:?}", res),
+        }
+
+        assert_eq!(
+            Callback::from_query("denied=rt"),
+            Some(Callback::Denied { token: "rt".into() })
+        );
+        assert_eq!(Callback::from_query("oauth_token=rt"), None);
+    }
+
+    #[cfg(feature = "hyper")]
+    #[tokio::test]
+    async fn listener() {
+        let listener = listen_callback(&([127, 0, 0, 1], 0).into()).unwrap();
+        let uri = format!("http://{}/callback", listener.local_addr());
+
+        let client = hyper_pkg::Client::new();
+        let favicon = format!("http://{}/favicon.ico", listener.local_addr());
+        let requests = tokio::spawn(async move {
+            let res = client.get(favicon.parse().unwrap()).await.unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+            let uri = format!("{}?oauth_token=rt&oauth_verifier=v", uri);
+            let res = client.get(uri.parse().unwrap()).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+        });
+
+        assert_eq!(
+            listener.await.unwrap(),
+            Callback::Approved {
+                token: "rt".into(),
+                verifier: "v".into(),
+            }
+        );
+        requests.await.unwrap();
+    }
+}
@@ -0,0 +1,381 @@
+//! Loading credentials and stream configurations from the environment and files.
+//!
+//! `Token`, `BearerToken`, `FilterLevel` and `Config` implement `Deserialize`, so they can
+//! be read from any format supported by `serde`. This module provides loaders for
+//! the conventional environment variables and for JSON and TOML (with the `toml` feature)
+//! files.
+//!
+//! This module requires the `serde` feature.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use twitter_stream::config::{self, Config};
+//! use twitter_stream::Token;
+//!
+//! # fn main() -> Result<(), config::Error> {
+//! let token = Token::from_env()?;
+//! let config: Config = config::from_file("stream.json")?;
+//! let future = config.builder(token).listen();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::env;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::de::{self, DeserializeOwned, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::Authorizer;
+use crate::types::{FilterLevel, Uri};
+use crate::util::not;
+use crate::Builder;
+
+/// An owned configuration of a `Builder` for the v1.1 streaming endpoints.
+///
+/// The fields correspond to the setter methods of `Builder`. All of them are optional
+/// when deserialized.
+///
+/// ```
+/// use twitter_stream::config::Config;
+///
+/// let config: Config = json::from_str(r#"{"track": "Rust", "follow": [12, 13]}"#).unwrap();
+/// assert_eq!(config.track.as_deref(), Some("Rust"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// The API endpoint to connect to instead of the default one.
+    #[serde(with = "uri", skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<Uri>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_line_len: Option<usize>,
+    #[serde(skip_serializing_if = "not")]
+    pub sniff_gzip: bool,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub clock_offset: i64,
+    #[serde(skip_serializing_if = "not")]
+    pub stall_warnings: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter_level: Option<FilterLevel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub follow: Vec<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track: Option<String>,
+    /// The bounding boxes in the form of `((longitude, latitude), (longitude, latitude))`
+    /// of the south-west and the north-east corners.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub locations: Vec<((f64, f64), (f64, f64))>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<i32>,
+}
+
+/// An error while loading a configuration.
+#[derive(Debug)]
+pub enum Error {
+    /// An error while reading a file.
+    Io(io::Error),
+    /// A malformed JSON file.
+    Json(json::Error),
+    /// A malformed TOML file.
+    #[cfg(feature = "toml")]
+    Toml(toml::de::Error),
+    /// The file has the `toml` extension but the `toml` feature is disabled.
+    UnknownFormat(PathBuf),
+    /// A required environment variable is not set.
+    MissingVar(String),
+    /// An environment variable has a malformed value.
+    InvalidVar(String),
+}
+
+/// Deserialize a value from a JSON or TOML file, depending on the extension of `path`.
+///
+/// Files with an extension other than `toml` are read as JSON if the `toml` feature is
+/// disabled.
+pub fn from_file<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, Error> {
+    let path = path.as_ref();
+    let is_toml = matches!(path.extension(), Some(ext) if ext == "toml");
+    let content = fs::read_to_string(path)?;
+    if is_toml {
+        #[cfg(feature = "toml")]
+        return toml::from_str(&content).map_err(Error::Toml);
+        #[cfg(not(feature = "toml"))]
+        return Err(Error::UnknownFormat(path.to_owned()));
+    }
+    json::from_str(&content).map_err(Error::Json)
+}
+
+impl Config {
+    /// Read the configuration from the environment variables named after the fields,
+    /// prefixed with `TWITTER_STREAM_` (e.g. `TWITTER_STREAM_TRACK`).
+    ///
+    /// The lists (`TWITTER_STREAM_FOLLOW` and `TWITTER_STREAM_LOCATIONS`) are comma-separated,
+    /// and each location consists of four numbers as in the `locations` parameter of the API
+    /// (e.g. `-122.75,36.8,-121.75,37.8`). Unset variables are left to the defaults.
+    pub fn from_env() -> Result<Self, Error> {
+        let mut config = Config::default();
+
+        if let Some(endpoint) = var("TWITTER_STREAM_ENDPOINT")? {
+            config.endpoint = Some(parse("TWITTER_STREAM_ENDPOINT", &endpoint)?);
+        }
+        if let Some(max_line_len) = var("TWITTER_STREAM_MAX_LINE_LEN")? {
+            config.max_line_len = Some(parse("TWITTER_STREAM_MAX_LINE_LEN", &max_line_len)?);
+        }
+        if let Some(sniff_gzip) = var("TWITTER_STREAM_SNIFF_GZIP")? {
+            config.sniff_gzip = parse("TWITTER_STREAM_SNIFF_GZIP", &sniff_gzip)?;
+        }
+        if let Some(clock_offset) = var("TWITTER_STREAM_CLOCK_OFFSET")? {
+            config.clock_offset = parse("TWITTER_STREAM_CLOCK_OFFSET", &clock_offset)?;
+        }
+        if let Some(stall_warnings) = var("TWITTER_STREAM_STALL_WARNINGS")? {
+            config.stall_warnings = parse("TWITTER_STREAM_STALL_WARNINGS", &stall_warnings)?;
+        }
+        config.filter_level = var("TWITTER_STREAM_FILTER_LEVEL")?.map(FilterLevel::from);
+        config.language = var("TWITTER_STREAM_LANGUAGE")?;
+        if let Some(follow) = var("TWITTER_STREAM_FOLLOW")? {
+            config.follow = parse_list("TWITTER_STREAM_FOLLOW", &follow)?;
+        }
+        config.track = var("TWITTER_STREAM_TRACK")?;
+        if let Some(locations) = var("TWITTER_STREAM_LOCATIONS")? {
+            let coordinates: Vec<f64> = parse_list("TWITTER_STREAM_LOCATIONS", &locations)?;
+            let chunks = coordinates.chunks_exact(4);
+            if !chunks.remainder().is_empty() {
+                return Err(Error::InvalidVar("TWITTER_STREAM_LOCATIONS".into()));
+            }
+            config.locations = chunks.map(|c| ((c[0], c[1]), (c[2], c[3]))).collect();
+        }
+        if let Some(count) = var("TWITTER_STREAM_COUNT")? {
+            config.count = Some(parse("TWITTER_STREAM_COUNT", &count)?);
+        }
+
+        Ok(config)
+    }
+
+    /// Returns `true` if the configuration has any of the predicates of the filter stream
+    /// (`follow`, `track` or `locations`).
+    pub fn is_filter(&self) -> bool {
+        !self.follow.is_empty() || self.track.is_some() || !self.locations.is_empty()
+    }
+
+    /// Create a `Builder` with this configuration.
+    ///
+    /// The builder is for the filter stream if `is_filter` returns `true`, and for
+    /// the sample stream otherwise.
+    pub fn builder<T: Authorizer>(&self, token: T) -> Builder<'_, T> {
+        let mut builder = if self.is_filter() {
+            Builder::filter(token)
+        } else {
+            Builder::sample(token)
+        };
+        if let Some(ref endpoint) = self.endpoint {
+            builder.endpoint(endpoint.clone());
+        }
+        builder
+            .max_line_len(self.max_line_len)
+            .sniff_gzip(self.sniff_gzip)
+            .clock_offset(self.clock_offset)
+            .stall_warnings(self.stall_warnings)
+            .filter_level(self.filter_level.clone())
+            .language(self.language.as_deref())
+            .follow(non_empty(&self.follow))
+            .track(self.track.as_deref())
+            .locations(non_empty(&self.locations))
+            .count(self.count);
+        builder
+    }
+}
+
+/// Returns the value of the environment variable `name`, or `None` if it is not set.
+pub(crate) fn var(name: &str) -> Result<Option<String>, Error> {
+    match env::var(name) {
+        Ok(value) => Ok(Some(value)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => Err(Error::InvalidVar(name.into())),
+    }
+}
+
+/// Returns the value of the environment variable `name`, which must be set.
+pub(crate) fn required_var(name: &str) -> Result<String, Error> {
+    var(name)?.ok_or_else(|| Error::MissingVar(name.into()))
+}
+
+fn parse<T: FromStr>(name: &str, value: &str) -> Result<T, Error> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| Error::InvalidVar(name.into()))
+}
+
+fn parse_list<T: FromStr>(name: &str, value: &str) -> Result<Vec<T>, Error> {
+    value.split(',').map(|v| parse(name, v)).collect()
+}
+
+fn non_empty<T>(list: &[T]) -> Option<&[T]> {
+    if list.is_empty() {
+        None
+    } else {
+        Some(list)
+    }
+}
+
+fn is_zero(n: &i64) -> bool {
+    *n == 0
+}
+
+mod uri {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(uri: &Option<Uri>, s: S) -> Result<S::Ok, S::Error> {
+        match *uri {
+            Some(ref uri) => s.collect_str(uri),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Uri>, D::Error> {
+        let uri: Option<String> = Deserialize::deserialize(d)?;
+        uri.map(|uri| uri.parse().map_err(de::Error::custom))
+            .transpose()
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => Display::fmt(e, f),
+            Error::Json(ref e) => Display::fmt(e, f),
+            #[cfg(feature = "toml")]
+            Error::Toml(ref e) => Display::fmt(e, f),
+            Error::UnknownFormat(ref path) => {
+                write!(f, "unknown configuration file format: {}", path.display())
+            }
+            Error::MissingVar(ref name) => write!(f, "environment variable {} is not set", name),
+            Error::InvalidVar(ref name) => write!(f, "environment variable {} is invalid", name),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            Error::Json(ref e) => Some(e),
+            #[cfg(feature = "toml")]
+            Error::Toml(ref e) => Some(e),
+            Error::UnknownFormat(_) | Error::MissingVar(_) | Error::InvalidVar(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::types::RequestMethod;
+    use crate::Token;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    fn expected() -> Config {
+        Config {
+            endpoint: Some(Uri::from_static("https://stream.example.com/filter.json")),
+            max_line_len: Some(1 << 20),
+            stall_warnings: true,
+            filter_level: Some(FilterLevel::Low),
+            follow: vec![12, 13],
+            track: Some("Rust".into()),
+            locations: vec![((-122.75, 36.8), (-121.75, 37.8))],
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn json_file() {
+        let config: Config = from_file(format!("{}/stream.json", FIXTURES)).unwrap();
+        assert_eq!(config, expected());
+
+        let config: Config = json::from_str(&json::to_string(&config).unwrap()).unwrap();
+        assert_eq!(config, expected());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_file() {
+        let config: Config = from_file(format!("{}/stream.toml", FIXTURES)).unwrap();
+        assert_eq!(config, expected());
+    }
+
+    #[test]
+    fn missing_file() {
+        match from_file::<Config>(format!("{}/missing.json", FIXTURES)) {
+            Err(Error::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            res => panic!("expected an I/O error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn env() {
+        let vars = [
+            (
+                "TWITTER_STREAM_ENDPOINT",
+                "https://stream.example.com/filter.json",
+            ),
+            ("TWITTER_STREAM_MAX_LINE_LEN", "1048576"),
+            ("TWITTER_STREAM_STALL_WARNINGS", "true"),
+            ("TWITTER_STREAM_FILTER_LEVEL", "low"),
+            ("TWITTER_STREAM_FOLLOW", "12,13"),
+            ("TWITTER_STREAM_TRACK", "Rust"),
+            ("TWITTER_STREAM_LOCATIONS", "-122.75,36.8,-121.75,37.8"),
+        ];
+        for &(name, value) in &vars {
+            env::set_var(name, value);
+        }
+        let config = Config::from_env();
+        env::set_var("TWITTER_STREAM_LOCATIONS", "-122.75,36.8");
+        let invalid = Config::from_env();
+        for &(name, _) in &vars {
+            env::remove_var(name);
+        }
+
+        assert_eq!(config.unwrap(), expected());
+        match invalid {
+            Err(Error::InvalidVar(name)) => assert_eq!(name, "TWITTER_STREAM_LOCATIONS"),
+            res => panic!("expected an invalid variable error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn builder() {
+        let token = Token::new("ck", "cs", "ak", "as");
+
+        let req = Config::default().builder(&token).build_request();
+        assert_eq!(req.method(), RequestMethod::GET);
+        assert_eq!(
+            req.uri(),
+            "https://stream.twitter.com/1.1/statuses/sample.json"
+        );
+
+        let req = expected().builder(&token).build_request();
+        assert_eq!(req.method(), RequestMethod::POST);
+        assert_eq!(req.uri(), "https://stream.example.com/filter.json");
+        assert_eq!(
+            req.body(),
+            b"filter_level=low&follow=12%2C13&locations=-122.75%2C36.8%2C-121.75%2C37.8&stall_warnings=true&track=Rust",
+        );
+    }
+}
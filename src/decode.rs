@@ -0,0 +1,500 @@
+// `async_compression::stream` is deprecated in favor of adapting `AsyncBufRead` decoders,
+// but it is the only interface that works directly on a `Stream` of `Bytes`.
+#![allow(deprecated)]
+
+use std::io;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[cfg(feature = "brotli")]
+use async_compression::stream::BrotliDecoder;
+use async_compression::stream::GzipDecoder;
+#[cfg(feature = "deflate")]
+use async_compression::stream::ZlibDecoder;
+#[cfg(feature = "zstd")]
+use async_compression::stream::ZstdDecoder;
+use bytes::{Bytes, BytesMut};
+use futures_core::{Stream, TryStream};
+use futures_util::ready;
+use futures_util::stream::{Fuse, IntoStream, StreamExt, TryStreamExt};
+use http::header::{HeaderMap, HeaderValue, CONTENT_ENCODING};
+use pin_project::pin_project;
+
+use crate::error::{DecodeError, DecodeErrorKind, Error};
+
+/// A stream that decodes the data from `S` according to the `Content-Encoding`
+/// of the response (or the content itself, if sniffing is enabled).
+#[pin_project]
+pub struct Decoder<S: TryStream<Ok = Bytes>> {
+    #[pin]
+    inner: Inner<S>,
+    state: State,
+}
+
+#[pin_project(project = InnerProj)]
+enum Inner<S: TryStream<Ok = Bytes>> {
+    Gzip(#[pin] GzipDecoder<Adapter<S>>),
+    #[cfg(feature = "deflate")]
+    Deflate(#[pin] ZlibDecoder<Adapter<S>>),
+    #[cfg(feature = "brotli")]
+    Brotli(#[pin] BrotliDecoder<Adapter<S>>),
+    #[cfg(feature = "zstd")]
+    Zstd(#[pin] ZstdDecoder<Adapter<S>>),
+    Identity(#[pin] Adapter<S>),
+}
+
+enum State {
+    Decode,
+    // Passing the data through a decoder that turned out to be unnecessary while sniffing.
+    Bypass,
+    // Buffering the first bytes of the body to look for the gzip magic number.
+    // The decoder is constructed beforehand so that `S` stays at the same location
+    // even if we decide to bypass the decoder after polling `S`.
+    Sniff(BytesMut),
+}
+
+#[pin_project]
+struct Adapter<S>
+where
+    S: TryStream,
+{
+    #[pin]
+    inner: Fuse<IntoStream<S>>,
+    // Data read while sniffing, to be fed to the decoder first.
+    head: Option<Bytes>,
+    // Number of bytes passed to the decoder.
+    pos: u64,
+    eof: bool,
+    // An error from `S`, which the decoder only sees as `TransportError`.
+    error: Option<S::Error>,
+}
+
+#[derive(Debug)]
+struct TransportError;
+
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+impl<S: TryStream<Ok = Bytes>> Decoder<S> {
+    /// Creates a `Decoder` for the content coding in `headers`, or a pass-through `Decoder`
+    /// if the body is not encoded.
+    ///
+    /// If `sniff_gzip` is `true` and the body is not encoded, the body is decoded as gzip
+    /// if it starts with the gzip magic number.
+    ///
+    /// Returns an error if the coding is not supported, or if more than one coding
+    /// other than `identity` is listed.
+    pub fn new(headers: &HeaderMap, sniff_gzip: bool, s: S) -> Result<Self, DecodeError> {
+        let mut codings = headers
+            .get_all(CONTENT_ENCODING)
+            .iter()
+            // A value that is not a valid string cannot name a supported coding.
+            .flat_map(|v| v.to_str().unwrap_or("<invalid>").split(','))
+            .map(str::trim)
+            .filter(|c| !c.is_empty() && !c.eq_ignore_ascii_case("identity"));
+        let coding = codings.next();
+        if let Some(second) = codings.next() {
+            let msg = format!(
+                "multiple content codings are not supported: {}, {}",
+                coding.unwrap(),
+                second
+            );
+            return Err(unsupported(msg));
+        }
+        if let Some(coding) = coding.filter(|c| !is_supported(c)) {
+            return Err(unsupported(format!(
+                "unsupported content coding: {}",
+                coding
+            )));
+        }
+        let s = Adapter {
+            inner: s.into_stream().fuse(),
+            head: None,
+            pos: 0,
+            eof: false,
+            error: None,
+        };
+
+        let inner = match coding {
+            Some(c) if c.eq_ignore_ascii_case("gzip") || c.eq_ignore_ascii_case("x-gzip") => {
+                gzip(s)
+            }
+            #[cfg(feature = "deflate")]
+            Some(c) if c.eq_ignore_ascii_case("deflate") => Inner::Deflate(ZlibDecoder::new(s)),
+            #[cfg(feature = "brotli")]
+            Some(c) if c.eq_ignore_ascii_case("br") => Inner::Brotli(BrotliDecoder::new(s)),
+            #[cfg(feature = "zstd")]
+            Some(c) if c.eq_ignore_ascii_case("zstd") => {
+                let mut decoder = ZstdDecoder::new(s);
+                decoder.multiple_members(true);
+                Inner::Zstd(decoder)
+            }
+            _ if sniff_gzip => {
+                let state = State::Sniff(BytesMut::new());
+                return Ok(Decoder {
+                    inner: gzip(s),
+                    state,
+                });
+            }
+            _ => Inner::Identity(s),
+        };
+
+        Ok(Decoder {
+            inner,
+            state: State::Decode,
+        })
+    }
+}
+
+fn unsupported(msg: String) -> DecodeError {
+    let e = io::Error::new(io::ErrorKind::InvalidData, msg);
+    DecodeError::new(DecodeErrorKind::Unsupported, 0, e)
+}
+
+fn gzip<S: TryStream<Ok = Bytes>>(s: Adapter<S>) -> Inner<S> {
+    let mut decoder = GzipDecoder::new(s);
+    // A proxy may restart the gzip stream in the middle of the response.
+    decoder.multiple_members(true);
+    Inner::Gzip(decoder)
+}
+
+impl<S: TryStream<Ok = Bytes>> Inner<S> {
+    fn adapter(self: Pin<&mut Self>) -> Pin<&mut Adapter<S>> {
+        match self.project() {
+            InnerProj::Gzip(d) => d.get_pin_mut(),
+            #[cfg(feature = "deflate")]
+            InnerProj::Deflate(d) => d.get_pin_mut(),
+            #[cfg(feature = "brotli")]
+            InnerProj::Brotli(d) => d.get_pin_mut(),
+            #[cfg(feature = "zstd")]
+            InnerProj::Zstd(d) => d.get_pin_mut(),
+            InnerProj::Identity(s) => s,
+        }
+    }
+}
+
+impl<S: TryStream<Ok = Bytes, Error = Error<E>>, E> Stream for Decoder<S> {
+    type Item = Result<Bytes, Error<E>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        loop {
+            match *this.state {
+                State::Decode => break,
+                State::Bypass => {
+                    let adapter = this.inner.as_mut().adapter().project();
+                    return adapter.inner.poll_next(cx);
+                }
+                State::Sniff(ref mut buf) => {
+                    let adapter = this.inner.as_mut().adapter().project();
+                    let next = ready!(adapter.inner.poll_next(cx)).transpose()?;
+                    if let Some(ref chunk) = next {
+                        buf.extend_from_slice(chunk);
+                        if buf.len() < GZIP_MAGIC.len() {
+                            continue;
+                        }
+                    }
+
+                    let head = mem::replace(buf, BytesMut::new()).freeze();
+                    if head.starts_with(&GZIP_MAGIC) {
+                        *adapter.head = Some(head);
+                        *this.state = State::Decode;
+                    } else {
+                        *this.state = State::Bypass;
+                        if !head.is_empty() {
+                            return Poll::Ready(Some(Ok(head)));
+                        } else if next.is_none() {
+                            return Poll::Ready(None);
+                        }
+                    }
+                }
+            }
+        }
+
+        let result = match this.inner.as_mut().project() {
+            InnerProj::Gzip(d) => ready!(d.poll_next(cx)),
+            #[cfg(feature = "deflate")]
+            InnerProj::Deflate(d) => ready!(d.poll_next(cx)),
+            #[cfg(feature = "brotli")]
+            InnerProj::Brotli(d) => ready!(d.poll_next(cx)),
+            #[cfg(feature = "zstd")]
+            InnerProj::Zstd(d) => ready!(d.poll_next(cx)),
+            InnerProj::Identity(s) => return s.project().inner.poll_next(cx),
+        };
+
+        Poll::Ready(result.map(|result| {
+            result.map_err(|e| {
+                let adapter = this.inner.as_mut().adapter().project();
+                // Recover the original error if the decoder failed because of an error of `S`.
+                if let Some(e) = adapter.error.take() {
+                    return e;
+                }
+                let kind = if *adapter.eof || e.kind() == io::ErrorKind::UnexpectedEof {
+                    DecodeErrorKind::Truncated
+                } else {
+                    DecodeErrorKind::Corrupt
+                };
+                Error::Decode(DecodeError::new(kind, *adapter.pos, e))
+            })
+        }))
+    }
+}
+
+impl<S: TryStream<Ok = Bytes>> Stream for Adapter<S> {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        let chunk = if let Some(head) = this.head.take() {
+            head
+        } else {
+            match ready!(this.inner.as_mut().poll_next(cx)) {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(e)) => {
+                    *this.error = Some(e);
+                    let e = io::Error::new(io::ErrorKind::ConnectionAborted, TransportError);
+                    return Poll::Ready(Some(Err(e)));
+                }
+                None => {
+                    *this.eof = true;
+                    return Poll::Ready(None);
+                }
+            }
+        };
+        *this.pos += chunk.len() as u64;
+        Poll::Ready(Some(Ok(chunk)))
+    }
+}
+
+/// Returns the default value of the `Accept-Encoding` header,
+/// listing the content codings supported by the enabled features.
+pub fn accept_encoding() -> HeaderValue {
+    let mut value = String::from("gzip");
+    if cfg!(feature = "zstd") {
+        value.push_str(", zstd");
+    }
+    if cfg!(feature = "brotli") {
+        value.push_str(", br");
+    }
+    if cfg!(feature = "deflate") {
+        value.push_str(", deflate");
+    }
+    HeaderValue::from_str(&value).unwrap()
+}
+
+impl std::error::Error for TransportError {}
+
+impl std::fmt::Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("error from the underlying stream")
+    }
+}
+
+fn is_supported(coding: &str) -> bool {
+    let supported: &[(&str, bool)] = &[
+        ("gzip", true),
+        ("x-gzip", true),
+        ("deflate", cfg!(feature = "deflate")),
+        ("br", cfg!(feature = "brotli")),
+        ("zstd", cfg!(feature = "zstd")),
+    ];
+    supported
+        .iter()
+        .any(|&(c, enabled)| enabled && coding.eq_ignore_ascii_case(c))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::convert::Infallible;
+
+    use futures_executor::{block_on, block_on_stream};
+    use futures_util::{future, stream};
+    use http::Response;
+
+    use crate::error::Backoff;
+    use crate::util::ChunksBody;
+    use crate::FutureTwitterStream;
+
+    const SINGLE: &[u8] = include_bytes!("../tests/fixtures/single.gz");
+    const MULTI_MEMBER: &[u8] = include_bytes!("../tests/fixtures/multi_member.gz");
+    const EXPECTED: [&str; 2] = ["{\"text\":\"first\"}", "{\"text\":\"second\"}"];
+
+    fn lines(body: &[u8], encoding: Option<&'static str>, sniff_gzip: bool) -> Vec<String> {
+        try_lines(body, encoding, sniff_gzip)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    fn try_lines(
+        body: &[u8],
+        encoding: Option<&'static str>,
+        sniff_gzip: bool,
+    ) -> Vec<Result<String, Error<Infallible>>> {
+        let stream = FutureTwitterStream::from_response(
+            future::ok::<_, ()>(response(body, encoding)),
+            None,
+            sniff_gzip,
+        );
+
+        block_on_stream(block_on(stream).unwrap())
+            .map(|line| line.map(|line| line.to_string()))
+            .collect()
+    }
+
+    fn response(
+        body: &[u8],
+        encoding: Option<&'static str>,
+    ) -> Response<ChunksBody<std::vec::IntoIter<Bytes>>> {
+        // Split the body into small chunks to exercise the boundaries.
+        let chunks = body
+            .chunks(7)
+            .map(Bytes::copy_from_slice)
+            .collect::<Vec<_>>();
+        let mut res = Response::new(ChunksBody(chunks.into_iter()));
+        if let Some(encoding) = encoding {
+            let encoding = HeaderValue::from_static(encoding);
+            res.headers_mut().insert(CONTENT_ENCODING, encoding);
+        }
+        res
+    }
+
+    fn decode_error(result: &Result<String, Error<Infallible>>) -> &DecodeError {
+        match *result {
+            Err(Error::Decode(ref e)) => e,
+            ref result => panic!("expected a decode error: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn content_encoding() {
+        assert_eq!(lines(SINGLE, Some("gzip"), false), EXPECTED);
+        assert_eq!(lines(SINGLE, Some("x-gzip"), false), EXPECTED);
+        assert_eq!(lines(SINGLE, Some("identity, GZip"), false), EXPECTED);
+
+        let plain = b"{\"text\":\"first\"}\r\n{\"text\":\"second\"}\r\n";
+        assert_eq!(lines(plain, Some("identity"), false), EXPECTED);
+        assert_eq!(lines(plain, Some("identity,"), false), EXPECTED);
+    }
+
+    #[test]
+    fn unsupported_coding() {
+        let plain = b"{\"text\":\"first\"}\r\n";
+        for &encoding in &[
+            "unknown",
+            "gzip, unknown",
+            "gzip, gzip",
+            "identity, x-gzip, br",
+        ] {
+            let stream = FutureTwitterStream::from_response(
+                future::ok::<_, ()>(response(plain, Some(encoding))),
+                None,
+                true,
+            );
+            match block_on(stream) {
+                Err(Error::Decode(ref e)) => {
+                    assert_eq!(e.kind(), DecodeErrorKind::Unsupported);
+                    assert_eq!(e.offset(), 0);
+                }
+                res => panic!(
+                    "expected an unsupported coding error: {:?}",
+                    res.map(|_| ())
+                ),
+            }
+        }
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn deflate() {
+        let body = include_bytes!("../tests/fixtures/single.zlib");
+        assert_eq!(lines(body, Some("deflate"), false), EXPECTED);
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn brotli() {
+        let body = include_bytes!("../tests/fixtures/single.br");
+        assert_eq!(lines(body, Some("br"), false), EXPECTED);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd() {
+        let body = include_bytes!("../tests/fixtures/single.zst");
+        assert_eq!(lines(body, Some("zstd"), false), EXPECTED);
+    }
+
+    #[test]
+    fn multi_member() {
+        assert_eq!(
+            lines(MULTI_MEMBER, Some("gzip"), false),
+            [
+                "{\"text\":\"first\"}",
+                "{\"text\":\"second\"}",
+                "{\"text\":\"third\"}",
+            ],
+        );
+    }
+
+    #[test]
+    fn sniff() {
+        assert_eq!(lines(SINGLE, None, true), EXPECTED);
+        assert_eq!(lines(SINGLE, Some("identity"), true), EXPECTED);
+
+        let plain = b"{\"text\":\"first\"}\r\n{\"text\":\"second\"}\r\n";
+        assert_eq!(lines(plain, None, true), EXPECTED);
+        assert_eq!(lines(b"1", None, true), ["1"]);
+        assert!(lines(b"", None, true).is_empty());
+    }
+
+    #[test]
+    fn corrupt() {
+        let mut body = SINGLE.to_owned();
+        for b in &mut body[12..20] {
+            *b = !*b;
+        }
+        let lines = try_lines(&body, Some("gzip"), false);
+        let e = decode_error(lines.last().unwrap());
+        assert_eq!(e.kind(), DecodeErrorKind::Corrupt);
+        assert!(e.offset() <= body.len() as u64);
+        assert_eq!(
+            lines.last().unwrap().as_ref().unwrap_err().backoff(),
+            Some(Backoff::Linear)
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let body = &SINGLE[..30];
+        let lines = try_lines(body, Some("gzip"), false);
+        let e = decode_error(lines.last().unwrap());
+        assert_eq!(e.kind(), DecodeErrorKind::Truncated);
+        assert_eq!(e.offset(), 30);
+    }
+
+    #[test]
+    fn transport_error() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        let chunks = vec![
+            Ok(Bytes::from_static(&SINGLE[..20])),
+            Err(Error::Service("connection reset")),
+        ];
+        let mut decoder = Decoder::new(&headers, false, stream::iter(chunks)).unwrap();
+
+        let result = loop {
+            match block_on(decoder.next()).unwrap() {
+                Ok(_) => continue,
+                Err(e) => break e,
+            }
+        };
+        match result {
+            Error::Service("connection reset") => {}
+            e => panic!("{:?}", e),
+        }
+    }
+}
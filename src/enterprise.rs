@@ -0,0 +1,314 @@
+//! Enterprise (Gnip) streaming endpoints.
+//!
+//! The enterprise streams use HTTP Basic authentication with the credentials of
+//! the console account, and are identified by the account name and the stream label.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use futures::prelude::*;
+//! use twitter_stream::auth::BasicAuth;
+//! use twitter_stream::enterprise::Builder;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let auth = BasicAuth::new("user@example.com", "password");
+//!
+//! Builder::decahose("my-account", "prod", 1, auth)
+//!     .backfill_minutes(5)
+//!     .listen()
+//!     .try_flatten_stream()
+//!     .try_for_each(|json| {
+//!         println!("{}", json);
+//!         future::ok(())
+//!     })
+//!     .await
+//!     .unwrap();
+//! # }
+//! ```
+
+use bytes::Bytes;
+use http::header::{HeaderName, HeaderValue};
+use http::{Request, Response};
+use http_body::Body;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use tower_service::Service;
+
+use crate::auth::{Authorizer, BasicAuth};
+use crate::types::{RequestMethod, Uri};
+use crate::{ClockSkewRetry, FutureTwitterStream};
+
+/// The number of partitions of the Decahose stream.
+pub const DECAHOSE_PARTITIONS: u32 = 2;
+
+/// The number of partitions of the compliance firehose.
+pub const COMPLIANCE_PARTITIONS: u32 = 8;
+
+/// The maximum number of minutes of activities that can be backfilled.
+pub const MAX_BACKFILL_MINUTES: u32 = 5;
+
+/// The characters to be escaped in the account name and the stream label.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// A builder for a `TwitterStream` connected to an enterprise streaming endpoint.
+#[derive(Clone, Debug)]
+pub struct Builder<'a, T = BasicAuth> {
+    inner: crate::Builder<'a, T, Params>,
+}
+
+#[derive(Clone, Debug, Default, oauth::Authorize)]
+struct Params {
+    #[oauth1(encoded, rename = "backfillMinutes")]
+    backfill_minutes: Option<u32>,
+    #[oauth1(encoded)]
+    partition: Option<u32>,
+}
+
+impl<'a, T: Authorizer> Builder<'a, T> {
+    /// Create a builder for the PowerTrack stream with the given account name and
+    /// stream label.
+    ///
+    /// The Tweets to be delivered are determined by the rules of the stream,
+    /// which are managed with the PowerTrack rules API.
+    ///
+    /// See the [Twitter Developer Documentation][1] for more information.
+    ///
+    /// [1]: https://developer.twitter.com/en/docs/twitter-api/enterprise/powertrack-api/api-reference/powertrack-stream
+    pub fn powertrack(account: &str, label: &str, auth: T) -> Self {
+        Builder::new("powertrack", account, label, None, auth)
+    }
+
+    /// Create a builder for a partition (`1..=DECAHOSE_PARTITIONS`) of the Decahose stream,
+    /// which delivers a random sample of 10% of all public Tweets.
+    ///
+    /// See the [Twitter Developer Documentation][1] for more information.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `partition` is out of range.
+    ///
+    /// [1]: https://developer.twitter.com/en/docs/twitter-api/enterprise/decahose-api/api-reference/decahose
+    pub fn decahose(account: &str, label: &str, partition: u32, auth: T) -> Self {
+        check_partition(partition, DECAHOSE_PARTITIONS);
+        Builder::new("sample10", account, label, Some(partition), auth)
+    }
+
+    /// Create a builder for a partition (`1..=COMPLIANCE_PARTITIONS`) of the compliance
+    /// firehose, which delivers the compliance events (deletions, withholdings, etc.)
+    /// for all public Tweets and users.
+    ///
+    /// See the [Twitter Developer Documentation][1] for more information.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `partition` is out of range.
+    ///
+    /// [1]: https://developer.twitter.com/en/docs/twitter-api/enterprise/compliance-firehose-api/api-reference/compliance-firehose
+    pub fn compliance(account: &str, label: &str, partition: u32, auth: T) -> Self {
+        check_partition(partition, COMPLIANCE_PARTITIONS);
+        Builder::new("compliance", account, label, Some(partition), auth)
+    }
+
+    fn new(product: &str, account: &str, label: &str, partition: Option<u32>, auth: T) -> Self {
+        let uri = format!(
+            "https://gnip-stream.twitter.com/stream/{}/accounts/{}/publishers/twitter/{}.json",
+            product,
+            utf8_percent_encode(account, PATH_SEGMENT),
+            utf8_percent_encode(label, PATH_SEGMENT),
+        );
+        Builder {
+            inner: crate::Builder::custom(RequestMethod::GET, uri.parse().unwrap(), auth)
+                .with_params(Params {
+                    backfill_minutes: None,
+                    partition,
+                }),
+        }
+    }
+
+    /// Start listening on the Streaming API endpoint, returning a `Future` which resolves
+    /// to a `Stream` yielding JSON messages from the API.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the underlying HTTPS connector failed to initialize.
+    #[cfg(feature = "hyper")]
+    pub fn listen(&self) -> crate::hyper::FutureTwitterStream {
+        let conn = hyper_tls::HttpsConnector::new();
+        self.listen_with_client(hyper_pkg::Client::builder().build::<_, hyper_pkg::Body>(conn))
+    }
+
+    /// Same as `listen` except that it uses `client` to make HTTP request to the endpoint.
+    ///
+    /// # Panics
+    ///
+    /// This will call `<S as Service>::call` without checking for `<S as Service>::poll_ready`
+    /// and may cause a panic if `client` is not ready to send an HTTP request yet.
+    pub fn listen_with_client<S, ReqB, ResB>(
+        &self,
+        client: S,
+    ) -> FutureTwitterStream<S::Future, ClockSkewRetry<S, ReqB>>
+    where
+        S: Service<Request<ReqB>, Response = Response<ResB>>,
+        ReqB: Default + From<Vec<u8>>,
+        ResB: Body<Data = Bytes>,
+    {
+        self.inner.listen_with_client(client)
+    }
+
+    /// Returns the signed request that `listen` would send, without sending it.
+    /// See `twitter_stream::Builder::build_request`.
+    pub fn build_request(&self) -> Request<Vec<u8>> {
+        self.inner.build_request()
+    }
+}
+
+impl<'a, T> Builder<'a, T> {
+    /// Reset the API endpoint URI to be connected.
+    pub fn endpoint(&mut self, endpoint: Uri) -> &mut Self {
+        self.inner.endpoint(endpoint);
+        self
+    }
+
+    /// Reset the credentials to be used to log into the stream.
+    pub fn token(&mut self, auth: T) -> &mut Self {
+        self.inner.token(auth);
+        self
+    }
+
+    /// Set the value of the `Accept-Encoding` header. See `twitter_stream::Builder::accept_encoding`.
+    ///
+    /// The enterprise streams require clients to accept gzip-compressed responses,
+    /// which is the default.
+    pub fn accept_encoding(
+        &mut self,
+        accept_encoding: impl Into<Option<HeaderValue>>,
+    ) -> &mut Self {
+        self.inner.accept_encoding(accept_encoding);
+        self
+    }
+
+    /// Add a header to be sent with the request. See `twitter_stream::Builder::header`.
+    pub fn header(&mut self, name: HeaderName, value: HeaderValue) -> &mut Self {
+        self.inner.header(name, value);
+        self
+    }
+
+    /// Add a request parameter that has no dedicated method.
+    /// See `twitter_stream::Builder::param`.
+    pub fn param(&mut self, key: &'a str, value: &'a str) -> &mut Self {
+        self.inner.param(key, value);
+        self
+    }
+
+    /// Set the maximum length of a line. See `twitter_stream::Builder::max_line_len`.
+    pub fn max_line_len(&mut self, max_line_len: impl Into<Option<usize>>) -> &mut Self {
+        self.inner.max_line_len(max_line_len);
+        self
+    }
+
+    /// Set whether to detect gzip-compressed responses by their content.
+    /// See `twitter_stream::Builder::sniff_gzip`.
+    pub fn sniff_gzip(&mut self, sniff_gzip: bool) -> &mut Self {
+        self.inner.sniff_gzip(sniff_gzip);
+        self
+    }
+
+    /// Set the number of minutes (up to `MAX_BACKFILL_MINUTES`) of activities to be delivered from before
+    /// the connection was made, to recover from a disconnection.
+    /// This parameter requires the Backfill add-on.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `backfill_minutes` is greater than `MAX_BACKFILL_MINUTES`.
+    pub fn backfill_minutes(&mut self, backfill_minutes: impl Into<Option<u32>>) -> &mut Self {
+        let backfill_minutes = backfill_minutes.into();
+        if let Some(minutes) = backfill_minutes {
+            assert!(
+                minutes <= MAX_BACKFILL_MINUTES,
+                "backfill_minutes must be in 0..={}, but was {}",
+                MAX_BACKFILL_MINUTES,
+                minutes,
+            );
+        }
+        self.inner.params.backfill_minutes = backfill_minutes;
+        self
+    }
+}
+
+fn check_partition(partition: u32, partitions: u32) {
+    assert!(
+        (1..=partitions).contains(&partition),
+        "partition must be in 1..={}, but was {}",
+        partitions,
+        partition,
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures_executor::block_on;
+
+    use crate::types::StatusCode;
+    use crate::util::{respond, ServiceFn};
+
+    fn uri<T: Authorizer>(builder: &Builder<'_, T>) -> String {
+        let mut uri = None;
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            assert_eq!(req.method(), RequestMethod::GET);
+            assert_eq!(req.headers()["authorization"], "Basic dXNlcjpwYXNz");
+            uri = Some(req.uri().to_string());
+            respond(StatusCode::OK, "")
+        });
+        block_on(builder.listen_with_client(client)).unwrap();
+        uri.unwrap()
+    }
+
+    #[test]
+    fn endpoints() {
+        let auth = BasicAuth::new("user", "pass");
+
+        assert_eq!(
+            uri(Builder::powertrack("acme", "prod", &auth).backfill_minutes(5)),
+            "https://gnip-stream.twitter.com/stream/powertrack/accounts/acme/publishers/twitter/prod.json?backfillMinutes=5",
+        );
+        assert_eq!(
+            uri(&Builder::decahose("acme", "dev 1", 2, &auth)),
+            "https://gnip-stream.twitter.com/stream/sample10/accounts/acme/publishers/twitter/dev%201.json?partition=2",
+        );
+        assert_eq!(
+            uri(Builder::compliance("acme", "prod", 8, &auth).backfill_minutes(1)),
+            "https://gnip-stream.twitter.com/stream/compliance/accounts/acme/publishers/twitter/prod.json?backfillMinutes=1&partition=8",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "partition must be in 1..=2, but was 3")]
+    fn decahose_partition() {
+        Builder::decahose("acme", "prod", 3, BasicAuth::new("user", "pass"));
+    }
+
+    #[test]
+    #[should_panic(expected = "partition must be in 1..=8, but was 0")]
+    fn compliance_partition() {
+        Builder::compliance("acme", "prod", 0, BasicAuth::new("user", "pass"));
+    }
+
+    #[test]
+    #[should_panic(expected = "backfill_minutes must be in 0..=5, but was 6")]
+    fn backfill_minutes() {
+        Builder::powertrack("acme", "prod", BasicAuth::new("user", "pass")).backfill_minutes(6);
+    }
+}
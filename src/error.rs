@@ -6,22 +6,67 @@ use std::io;
 use std::str::Utf8Error;
 
 use crate::types::StatusCode;
+use crate::v2::Problem;
 
 /// An error occurred while trying to connect to a Stream.
 #[derive(Debug)]
 pub enum Error<E = Box<dyn error::Error + Send + Sync>> {
-    /// An error occured while decoding gzip stream from the server.
-    Gzip(io::Error),
+    /// An error occured while decoding the compressed (e.g. gzip) stream from the server.
+    Decode(DecodeError),
     /// An HTTP error from the Stream.
     Http(StatusCode),
+    /// An error response with a problem object from a Twitter API v2 endpoint.
+    Api(Problem),
     /// Error from the underlying HTTP client while receiving an HTTP response or reading the body.
     Service(E),
     /// Twitter returned a non-UTF-8 string.
     Utf8(Utf8Error),
+    /// Twitter returned a line longer than the limit set with `Builder::max_line_len`.
+    /// The value is the length of the line in bytes.
+    LineTooLong(usize),
     /// User-defined error.
     Custom(Box<dyn error::Error + Send + Sync>),
 }
 
+/// An error occurred while decoding the compressed response body.
+#[derive(Debug)]
+pub struct DecodeError {
+    kind: DecodeErrorKind,
+    offset: u64,
+    error: io::Error,
+}
+
+/// The cause of a `DecodeError`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DecodeErrorKind {
+    /// The compressed data is corrupt.
+    Corrupt,
+    /// The response body ended in the middle of the compressed data,
+    /// i.e. the connection was closed prematurely.
+    Truncated,
+    /// The response body is encoded with a content coding that is not supported,
+    /// or with more than one content coding.
+    Unsupported,
+}
+
+/// The back-off strategy that Twitter recommends before reconnecting after an error.
+///
+/// See the [Twitter Developer Documentation][1] for more information.
+///
+/// [1]: https://developer.twitter.com/en/docs/tweets/filter-realtime/guides/connecting
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Backoff {
+    /// Back off linearly, starting from 250 milliseconds up to 16 seconds.
+    /// This is for network errors.
+    Linear,
+    /// Back off exponentially, starting from 5 seconds up to 320 seconds.
+    /// This is for HTTP errors.
+    Exponential,
+    /// Back off exponentially, starting from 1 minute.
+    /// This is for rate limiting (HTTP 420 and 429).
+    RateLimited,
+}
+
 impl<E> Error<E> {
     pub fn custom<C>(error: C) -> Self
     where
@@ -29,6 +74,50 @@ impl<E> Error<E> {
     {
         Error::Custom(error.into())
     }
+
+    /// Returns how to back off before reconnecting after this error,
+    /// or `None` if reconnecting would not help (e.g. the request is unauthorized).
+    ///
+    /// A failure to decode the compressed stream is treated as a network error,
+    /// because a corrupt or truncated stream is usually caused by a broken connection.
+    pub fn backoff(&self) -> Option<Backoff> {
+        match *self {
+            Error::Decode(ref e) if e.kind == DecodeErrorKind::Unsupported => None,
+            Error::Decode(_) | Error::Service(_) => Some(Backoff::Linear),
+            Error::Http(status) | Error::Api(Problem { status, .. }) => match status.as_u16() {
+                420 | 429 => Some(Backoff::RateLimited),
+                401 | 403 | 404 | 406 | 413 | 416 => None,
+                _ => Some(Backoff::Exponential),
+            },
+            Error::Utf8(_) | Error::LineTooLong(_) | Error::Custom(_) => None,
+        }
+    }
+}
+
+impl DecodeError {
+    pub(crate) fn new(kind: DecodeErrorKind, offset: u64, error: io::Error) -> Self {
+        DecodeError {
+            kind,
+            offset,
+            error,
+        }
+    }
+
+    /// Returns whether the data was corrupt, truncated or in an unsupported coding.
+    pub fn kind(&self) -> DecodeErrorKind {
+        self.kind
+    }
+
+    /// Returns the number of bytes of the compressed response body
+    /// that had been received when the error occurred.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the error reported by the decoder.
+    pub fn io_error(&self) -> &io::Error {
+        &self.error
+    }
 }
 
 impl<E: error::Error + 'static> error::Error for Error<E> {
@@ -36,10 +125,12 @@ impl<E: error::Error + 'static> error::Error for Error<E> {
         use crate::Error::*;
 
         match *self {
-            Gzip(ref e) => e.description(),
+            Decode(_) => "failed to decode the response body",
             Http(ref status) => status.canonical_reason().unwrap_or("<unknown status code>"),
+            Api(ref problem) => &problem.title,
             Service(ref e) => e.description(),
             Utf8(ref e) => e.description(),
+            LineTooLong(_) => "line too long",
             Custom(ref e) => e.description(),
         }
     }
@@ -48,10 +139,12 @@ impl<E: error::Error + 'static> error::Error for Error<E> {
         use crate::Error::*;
 
         match *self {
-            Gzip(ref e) => Some(e),
+            Decode(ref e) => Some(e),
             Http(_) => None,
+            Api(ref e) => Some(e),
             Service(ref e) => Some(e),
             Utf8(ref e) => Some(e),
+            LineTooLong(_) => None,
             Custom(ref e) => Some(&**e),
         }
     }
@@ -62,11 +155,34 @@ impl<E: Display> Display for Error<E> {
         use crate::Error::*;
 
         match *self {
-            Gzip(ref e) => Display::fmt(e, f),
+            Decode(ref e) => Display::fmt(e, f),
             Http(ref code) => Display::fmt(code, f),
+            Api(ref e) => Display::fmt(e, f),
             Service(ref e) => Display::fmt(e, f),
             Utf8(ref e) => Display::fmt(e, f),
+            LineTooLong(len) => write!(f, "line too long ({} bytes)", len),
             Custom(ref e) => Display::fmt(e, f),
         }
     }
 }
+
+impl error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            DecodeErrorKind::Corrupt => "corrupt",
+            DecodeErrorKind::Truncated => "truncated",
+            DecodeErrorKind::Unsupported => return write!(f, "{}", self.error),
+        };
+        write!(
+            f,
+            "{} compressed stream at byte {}: {}",
+            kind, self.offset, self.error
+        )
+    }
+}
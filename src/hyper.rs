@@ -1,5 +1,9 @@
 //! Type aliases for use with `hyper` crate's HTTP client.
 
-pub type FutureTwitterStream = crate::FutureTwitterStream<hyper_pkg::client::ResponseFuture>;
+pub type FutureTwitterStream = crate::FutureTwitterStream<
+    hyper_pkg::client::ResponseFuture,
+    crate::ClockSkewRetry<Client, hyper_pkg::Body>,
+>;
+pub type Client = hyper_pkg::Client<hyper_tls::HttpsConnector<hyper_pkg::client::HttpConnector>>;
 pub type Error = crate::Error<hyper_pkg::Error>;
 pub type TwitterStream = crate::TwitterStream<hyper_pkg::Body>;
@@ -16,6 +16,22 @@ tokio = { version = "0.2", features = ["macros"] }
 twitter-stream = "=0.10.0-alpha.4"
 ```
 
+## Features
+
+The following Cargo features are available:
+
+- `hyper` (default): `Builder::listen`, which sends the request with a `hyper` client.
+- `brotli`, `deflate` and `zstd`: decoding of response bodies in these codings
+  (gzip is always supported).
+- `serde`: (de)serialization of `Token`, `BearerToken` and `FilterLevel`, and
+  the `config` module for loading them from files and environment variables.
+- `toml`: loading TOML files in the `config` module (together with `serde`).
+- `zeroize`: the `auth::Secret` string type, which is zeroed when dropped.
+
+Note that `serde` and `serde_json` are always dependencies of this library, which uses them
+internally (e.g. to parse the v2 API responses). The `serde` feature only adds the public
+(de)serialization support and does not remove them from the dependency graph.
+
 ## Overview
 
 Here is a basic example that prints public mentions to @Twitter in JSON format:
@@ -48,20 +64,34 @@ extern crate hyper_pkg;
 #[macro_use]
 mod util;
 
+pub mod auth;
+#[cfg(feature = "serde")]
+pub mod config;
+pub mod enterprise;
 pub mod error;
 #[cfg(feature = "hyper")]
 pub mod hyper;
+pub mod normalize;
 pub mod types;
+pub mod v2;
+pub mod webhook;
 
-mod gzip;
+mod decode;
+mod raw;
+mod skew;
 mod token;
+mod tolerant;
 
 pub use oauth::Credentials;
 
+pub use crate::auth::BearerToken;
 pub use crate::error::Error;
+pub use crate::raw::RawTwitterStream;
+pub use crate::skew::ClockSkewRetry;
 pub use crate::token::Token;
+pub use crate::tolerant::{Malformed, MalformedLine, Tolerant};
 
-use std::borrow::Borrow;
+use std::fmt::{self, Debug, Formatter};
 use std::future::Future;
 use std::pin::Pin;
 use std::str;
@@ -71,15 +101,18 @@ use bytes::Bytes;
 use futures_core::Stream;
 use futures_util::ready;
 use http::header::{
-    HeaderValue, ACCEPT_ENCODING, AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE,
+    HeaderMap, HeaderName, HeaderValue, ACCEPT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE,
 };
 use http::response::Parts;
 use http::{Request, Response};
 use http_body::Body;
+use percent_encoding::utf8_percent_encode;
 use pin_project::pin_project;
 use tower_service::Service;
 
-use crate::gzip::MaybeGzip;
+use crate::auth::{Authorizer, ConnectionEvent, SignatureBase};
+use crate::decode::Decoder;
+use crate::skew::Reconnect;
 use crate::types::{FilterLevel, RequestMethod, StatusCode, Uri};
 use crate::util::*;
 
@@ -106,20 +139,38 @@ use crate::util::*;
 ///     .unwrap();
 /// # }
 /// ```
-#[derive(Clone, Debug)]
-pub struct Builder<'a, T = Token> {
+#[derive(Clone)]
+pub struct Builder<'a, T = Token, P = StreamParams<'a>> {
     method: RequestMethod,
     endpoint: Uri,
     token: T,
-    inner: BuilderInner<'a>,
+    accept_encoding: Option<HeaderValue>,
+    headers: HeaderMap,
+    max_line_len: Option<usize>,
+    sniff_gzip: bool,
+    clock_offset: i64,
+    params: P,
+    extra_params: Vec<(&'a str, &'a str)>,
 }
 
 /// A future returned by constructor methods
 /// which resolves to a `TwitterStream`.
+///
+/// If the request is rejected because of a skew of the local clock, the future connects
+/// again once with corrected timestamps (see `ClockSkewRetry`).
+///
+/// The outcome of the connection is reported to the authorizer returned by
+/// `Authorizer::for_connection`, if any.
 #[pin_project]
-pub struct FutureTwitterStream<F> {
+pub struct FutureTwitterStream<F, R = ()> {
+    // `None` if the request was not sent because the parameters are invalid.
     #[pin]
-    response: F,
+    response: Option<F>,
+    max_line_len: Option<usize>,
+    sniff_gzip: bool,
+    retry: Option<R>,
+    error: Option<Box<dyn std::error::Error + Send + Sync>>,
+    authorizer: Option<Box<dyn Authorizer + Send + Sync>>,
 }
 
 /// A listener for Twitter Streaming API.
@@ -127,11 +178,16 @@ pub struct FutureTwitterStream<F> {
 #[pin_project]
 pub struct TwitterStream<B: Body<Data = Bytes>> {
     #[pin]
-    inner: Lines<MaybeGzip<HttpBodyAsStream<B>>>,
+    inner: Lines<Decoder<HttpBodyAsStream<B>>>,
+    offset: u64,
+    // The authorizer to report `disconnect` messages to.
+    authorizer: Option<Box<dyn Authorizer + Send + Sync>>,
 }
 
-#[derive(Clone, Debug, oauth::Authorize)]
-struct BuilderInner<'a> {
+/// The parameters of the v1.1 `statuses/filter` and `statuses/sample` endpoints,
+/// which are set with the respective methods of `Builder`.
+#[derive(Clone, Debug, Default, oauth::Authorize)]
+pub struct StreamParams<'a> {
     #[oauth1(skip_if = "not")]
     stall_warnings: bool,
     filter_level: Option<FilterLevel>,
@@ -146,17 +202,13 @@ struct BuilderInner<'a> {
     count: Option<i32>,
 }
 
-impl<'a, C, A> Builder<'a, Token<C, A>>
-where
-    C: Borrow<str>,
-    A: Borrow<str>,
-{
+impl<'a, T: Authorizer> Builder<'a, T> {
     /// Create a builder for `POST statuses/filter` endpoint.
     ///
     /// See the [Twitter Developer Documentation][1] for more information.
     ///
     /// [1]: https://dev.twitter.com/streaming/reference/post/statuses/filter
-    pub fn filter(token: Token<C, A>) -> Self {
+    pub fn filter(token: T) -> Self {
         const URI: &str = "https://stream.twitter.com/1.1/statuses/filter.json";
         Self::custom(RequestMethod::POST, Uri::from_static(URI), token)
     }
@@ -166,29 +218,29 @@ where
     /// See the [Twitter Developer Documentation][1] for more information.
     ///
     /// [1]: https://dev.twitter.com/streaming/reference/get/statuses/sample
-    pub fn sample(token: Token<C, A>) -> Self {
+    pub fn sample(token: T) -> Self {
         const URI: &str = "https://stream.twitter.com/1.1/statuses/sample.json";
         Self::custom(RequestMethod::GET, Uri::from_static(URI), token)
     }
 
     /// Constructs a builder for a Stream at a custom endpoint.
-    pub fn custom(method: RequestMethod, endpoint: Uri, token: Token<C, A>) -> Self {
+    pub fn custom(method: RequestMethod, endpoint: Uri, token: T) -> Self {
         Self {
             method,
             endpoint,
             token,
-            inner: BuilderInner {
-                stall_warnings: false,
-                filter_level: None,
-                language: None,
-                follow: None,
-                track: None,
-                locations: None,
-                count: None,
-            },
+            accept_encoding: Some(decode::accept_encoding()),
+            headers: HeaderMap::new(),
+            max_line_len: None,
+            sniff_gzip: false,
+            clock_offset: 0,
+            params: StreamParams::default(),
+            extra_params: Vec::new(),
         }
     }
+}
 
+impl<'a, T: Authorizer, P: oauth::Authorize> Builder<'a, T, P> {
     /// Start listening on the Streaming API endpoint, returning a `Future` which resolves
     /// to a `Stream` yielding JSON messages from the API.
     ///
@@ -207,51 +259,147 @@ where
     ///
     /// This will call `<S as Service>::call` without checking for `<S as Service>::poll_ready`
     /// and may cause a panic if `client` is not ready to send an HTTP request yet.
-    pub fn listen_with_client<S, ReqB, ResB>(&self, mut client: S) -> FutureTwitterStream<S::Future>
+    pub fn listen_with_client<S, ReqB, ResB>(
+        &self,
+        client: S,
+    ) -> FutureTwitterStream<S::Future, ClockSkewRetry<S, ReqB>>
     where
         S: Service<Request<ReqB>, Response = Response<ResB>>,
         ReqB: Default + From<Vec<u8>>,
         ResB: Body<Data = Bytes>,
     {
-        let req = Request::builder()
-            .method(self.method.clone())
-            .header(ACCEPT_ENCODING, HeaderValue::from_static("gzip"));
-
-        let mut oauth = oauth::Builder::new(self.token.client.as_ref(), oauth::HmacSha1);
-        oauth.token(self.token.token.as_ref());
-        let req = if RequestMethod::POST == self.method {
-            let oauth::Request {
-                authorization,
-                data,
-            } = oauth.post_form(&self.endpoint, &self.inner);
-
-            req.uri(self.endpoint.clone())
-                .header(AUTHORIZATION, authorization)
-                .header(
-                    CONTENT_TYPE,
-                    HeaderValue::from_static("application/x-www-form-urlencoded"),
-                )
-                .header(CONTENT_LENGTH, data.len())
-                .body(data.into_bytes().into())
-                .unwrap()
-        } else {
-            let oauth::Request {
-                authorization,
-                data: uri,
-            } = oauth.build(self.method.as_ref(), &self.endpoint, &self.inner);
-
-            req.uri(uri)
-                .header(AUTHORIZATION, authorization)
-                .body(ReqB::default())
-                .unwrap()
-        };
+        self.connect(client)
+    }
+
+    /// Returns the signed request that `listen` would send, without sending it.
+    ///
+    /// This is useful for sending the request with an HTTP client that is not
+    /// a `tower_service::Service`. Note that the request contains the credentials
+    /// in the `Authorization` header.
+    pub fn build_request(&self) -> Request<Vec<u8>> {
+        self.request(&self.token, &self.encoded_params())
+    }
+
+    /// Returns the OAuth 1.0a signature base string and the normalized parameters of
+    /// the request that `listen` would send, or `None` if the token does not sign requests.
+    ///
+    /// Each call signs the request with a fresh nonce and timestamp unless the token is
+    /// an `auth::Deterministic`.
+    pub fn signature_base(&self) -> Option<SignatureBase> {
+        let params = self.encoded_params();
+        self.token
+            .signature_base(&self.method, &self.endpoint, &params, self.clock_offset)
+    }
+
+    /// Make a request with the given encoded parameters to the endpoint, signed by `token`.
+    fn request<ReqB>(&self, token: &dyn Authorizer, params: &str) -> Request<ReqB>
+    where
+        ReqB: Default + From<Vec<u8>>,
+    {
+        let authorization = token.authorize_with_clock_offset(
+            &self.method,
+            &self.endpoint,
+            params,
+            self.clock_offset,
+        );
+        let mut headers = self.headers();
+        headers.extend(authorization);
+        make_request(&self.method, &self.endpoint, params, headers)
+    }
+
+    /// Encode the parameters, followed by the extra parameters.
+    fn encoded_params(&self) -> String {
+        let mut params = encode_params(&self.params);
+        for &(key, value) in &self.extra_params {
+            if !params.is_empty() {
+                params.push('&');
+            }
+            params.extend(utf8_percent_encode(key, UNRESERVED));
+            params.push('=');
+            params.extend(utf8_percent_encode(value, UNRESERVED));
+        }
+        params
+    }
+
+    /// Returns the headers to be sent except for the `Authorization` header.
+    fn headers(&self) -> HeaderMap {
+        let mut headers = self.headers.clone();
+        if let Some(ref accept_encoding) = self.accept_encoding {
+            headers.insert(ACCEPT_ENCODING, accept_encoding.clone());
+        }
+        headers
+    }
+
+    /// Send a request to the endpoint.
+    fn connect<S, ReqB, ResB>(
+        &self,
+        mut client: S,
+    ) -> FutureTwitterStream<S::Future, ClockSkewRetry<S, ReqB>>
+    where
+        S: Service<Request<ReqB>, Response = Response<ResB>>,
+        ReqB: Default + From<Vec<u8>>,
+        ResB: Body<Data = Bytes>,
+    {
+        let params = self.encoded_params();
+        let authorizer = self.token.for_connection();
+        let token = authorizer
+            .as_ref()
+            .map_or(&self.token as &dyn Authorizer, |a| &**a);
+        let response = client.call(self.request(token, &params));
+        let retry = token.clock_dependent().map(|authorizer| {
+            ClockSkewRetry::new(
+                client,
+                authorizer,
+                self.method.clone(),
+                self.endpoint.clone(),
+                params,
+                self.headers(),
+                self.clock_offset,
+            )
+        });
+        FutureTwitterStream {
+            response: Some(response),
+            max_line_len: self.max_line_len,
+            sniff_gzip: self.sniff_gzip,
+            retry,
+            error: None,
+            authorizer,
+        }
+    }
+}
 
-        let response = client.call(req);
-        FutureTwitterStream { response }
+/// Make a request to `endpoint` with the encoded parameters and the `headers`.
+fn make_request<ReqB>(
+    method: &RequestMethod,
+    endpoint: &Uri,
+    params: &str,
+    headers: HeaderMap,
+) -> Request<ReqB>
+where
+    ReqB: Default + From<Vec<u8>>,
+{
+    let mut req = Request::builder().method(method.clone());
+    req.headers_mut().unwrap().extend(headers);
+
+    if RequestMethod::POST == method {
+        req.uri(endpoint.clone())
+            .header(
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/x-www-form-urlencoded"),
+            )
+            .header(CONTENT_LENGTH, params.len())
+            .body(params.as_bytes().to_vec().into())
+            .unwrap()
+    } else if params.is_empty() {
+        req.uri(endpoint.clone()).body(ReqB::default()).unwrap()
+    } else {
+        req.uri(format!("{}?{}", endpoint, params))
+            .body(ReqB::default())
+            .unwrap()
     }
 }
 
-impl<'a, C, A> Builder<'a, Token<C, A>> {
+impl<'a, T, P> Builder<'a, T, P> {
     /// Reset the HTTP request method to be used when connecting
     /// to the server.
     pub fn method(&mut self, method: RequestMethod) -> &mut Self {
@@ -266,11 +414,127 @@ impl<'a, C, A> Builder<'a, Token<C, A>> {
     }
 
     /// Reset the token to be used to log into Twitter.
-    pub fn token(&mut self, token: Token<C, A>) -> &mut Self {
+    pub fn token(&mut self, token: T) -> &mut Self {
         self.token = token;
         self
     }
 
+    /// Set the value of the `Accept-Encoding` header, or `None` to omit the header.
+    ///
+    /// The default lists the content codings that the crate is built to decode: `gzip`,
+    /// plus `zstd`, `br` and `deflate` if the respective Cargo features are enabled.
+    /// A response body in any of these codings is decoded according to its `Content-Encoding`.
+    /// A response in any other coding, or in more than one coding, is rejected with
+    /// `Error::Decode`.
+    pub fn accept_encoding(
+        &mut self,
+        accept_encoding: impl Into<Option<HeaderValue>>,
+    ) -> &mut Self {
+        self.accept_encoding = accept_encoding.into();
+        self
+    }
+
+    /// Add a header to be sent with the request.
+    ///
+    /// A header with the same name is sent multiple times. The `Authorization` and
+    /// `Accept-Encoding` headers are overridden by the token and `accept_encoding` respectively,
+    /// and the `Content-Type` and `Content-Length` headers should not be set.
+    pub fn header(&mut self, name: HeaderName, value: HeaderValue) -> &mut Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Add a request parameter that has no dedicated method (e.g. `delimited` or `with`).
+    ///
+    /// The parameter is percent-encoded and signed along with the other parameters.
+    /// The key is not checked against the parameters set by the dedicated methods or by
+    /// earlier calls: if it is set by both (e.g. `track` and `param("track", ..)`), the request
+    /// contains the key twice, which the API may reject. Use the dedicated method instead.
+    pub fn param(&mut self, key: &'a str, value: &'a str) -> &mut Self {
+        self.extra_params.push((key, value));
+        self
+    }
+
+    /// Set the maximum length in bytes of a line the stream accepts.
+    ///
+    /// A longer line is dropped without being buffered as a whole, and the stream
+    /// yields `Error::LineTooLong` in its place (or reports it as `Malformed::TooLong`
+    /// if the stream is made `tolerant`). The default is no limit.
+    pub fn max_line_len(&mut self, max_line_len: impl Into<Option<usize>>) -> &mut Self {
+        self.max_line_len = max_line_len.into();
+        self
+    }
+
+    /// Set whether to detect gzip-compressed response bodies by their content
+    /// when the `Content-Encoding` header does not indicate gzip.
+    ///
+    /// This is useful behind proxies that strip the header but leave the body compressed.
+    /// The default is `false`.
+    pub fn sniff_gzip(&mut self, sniff_gzip: bool) -> &mut Self {
+        self.sniff_gzip = sniff_gzip;
+        self
+    }
+
+    /// Set the number of seconds by which the server clock is ahead of the local clock.
+    ///
+    /// This corrects the `oauth_timestamp` of requests signed with a `Token`, which are
+    /// rejected if the local clock is off by too much. The offset is also detected
+    /// automatically from the `Date` header of a `401 Unauthorized` response,
+    /// in which case the request is retried once. The default is `0`.
+    pub fn clock_offset(&mut self, clock_offset: i64) -> &mut Self {
+        self.clock_offset = clock_offset;
+        self
+    }
+
+    /// Replace the parameters of the request with `params`, keeping the other settings.
+    ///
+    /// This is for endpoints whose parameters differ from those of `statuses/filter`.
+    /// The parameters are encoded and signed with the `oauth::Authorize` implementation
+    /// of `P`, which can be derived with `#[derive(oauth::Authorize)]`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use twitter_stream::{Builder, Token};
+    /// use twitter_stream::types::{RequestMethod, Uri};
+    ///
+    /// #[derive(oauth::Authorize)]
+    /// struct SiteParams<'a> {
+    ///     follow: &'a str,
+    ///     with: Option<&'a str>,
+    /// }
+    ///
+    /// # let token = Token::new("", "", "", "");
+    /// let endpoint = Uri::from_static("https://sitestream.twitter.com/1.1/site.json");
+    /// let future = Builder::custom(RequestMethod::GET, endpoint, token)
+    ///     .with_params(SiteParams {
+    ///         follow: "12,13",
+    ///         with: Some("followings"),
+    ///     })
+    ///     .listen();
+    /// ```
+    pub fn with_params<Q>(self, params: Q) -> Builder<'a, T, Q> {
+        Builder {
+            method: self.method,
+            endpoint: self.endpoint,
+            token: self.token,
+            accept_encoding: self.accept_encoding,
+            headers: self.headers,
+            max_line_len: self.max_line_len,
+            sniff_gzip: self.sniff_gzip,
+            clock_offset: self.clock_offset,
+            params,
+            extra_params: self.extra_params,
+        }
+    }
+
+    /// Returns a mutable reference to the parameters of the request.
+    pub fn params_mut(&mut self) -> &mut P {
+        &mut self.params
+    }
+}
+
+impl<'a, T> Builder<'a, T> {
     /// Set whether to receive messages when in danger of
     /// being disconnected.
     ///
@@ -278,7 +542,7 @@ impl<'a, C, A> Builder<'a, Token<C, A>> {
     ///
     /// [1]: https://developer.twitter.com/en/docs/tweets/filter-realtime/guides/basic-stream-parameters#stall-warnings
     pub fn stall_warnings(&mut self, stall_warnings: bool) -> &mut Self {
-        self.inner.stall_warnings = stall_warnings;
+        self.params.stall_warnings = stall_warnings;
         self
     }
 
@@ -289,7 +553,7 @@ impl<'a, C, A> Builder<'a, Token<C, A>> {
     ///
     /// [1]: https://developer.twitter.com/en/docs/tweets/filter-realtime/guides/basic-stream-parameters#filter-level
     pub fn filter_level(&mut self, filter_level: impl Into<Option<FilterLevel>>) -> &mut Self {
-        self.inner.filter_level = filter_level.into();
+        self.params.filter_level = filter_level.into();
         self
     }
 
@@ -300,7 +564,7 @@ impl<'a, C, A> Builder<'a, Token<C, A>> {
     ///
     /// [1]: https://developer.twitter.com/en/docs/tweets/filter-realtime/guides/basic-stream-parameters#language
     pub fn language(&mut self, language: impl Into<Option<&'a str>>) -> &mut Self {
-        self.inner.language = language.into();
+        self.params.language = language.into();
         self
     }
 
@@ -310,7 +574,7 @@ impl<'a, C, A> Builder<'a, Token<C, A>> {
     ///
     /// [1]: https://developer.twitter.com/en/docs/tweets/filter-realtime/guides/basic-stream-parameters#follow
     pub fn follow(&mut self, follow: impl Into<Option<&'a [u64]>>) -> &mut Self {
-        self.inner.follow = follow.into();
+        self.params.follow = follow.into();
         self
     }
 
@@ -320,7 +584,7 @@ impl<'a, C, A> Builder<'a, Token<C, A>> {
     ///
     /// [1]: https://developer.twitter.com/en/docs/tweets/filter-realtime/guides/basic-stream-parameters#track
     pub fn track(&mut self, track: impl Into<Option<&'a str>>) -> &mut Self {
-        self.inner.track = track.into();
+        self.params.track = track.into();
         self
     }
 
@@ -335,7 +599,7 @@ impl<'a, C, A> Builder<'a, Token<C, A>> {
         &mut self,
         locations: impl Into<Option<&'a [((f64, f64), (f64, f64))]>>,
     ) -> &mut Self {
-        self.inner.locations = locations.into();
+        self.params.locations = locations.into();
         self
     }
 
@@ -346,11 +610,29 @@ impl<'a, C, A> Builder<'a, Token<C, A>> {
     ///
     /// [1]: https://developer.twitter.com/en/docs/tweets/filter-realtime/guides/basic-stream-parameters#count
     pub fn count(&mut self, count: impl Into<Option<i32>>) -> &mut Self {
-        self.inner.count = count.into();
+        self.params.count = count.into();
         self
     }
 }
 
+impl<'a, T, P: Debug> Debug for Builder<'a, T, P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // The token is left out because it may contain secrets.
+        f.debug_struct("Builder")
+            .field("method", &self.method)
+            .field("endpoint", &self.endpoint)
+            .field("token", &Hidden)
+            .field("accept_encoding", &self.accept_encoding)
+            .field("headers", &self.headers)
+            .field("max_line_len", &self.max_line_len)
+            .field("sniff_gzip", &self.sniff_gzip)
+            .field("clock_offset", &self.clock_offset)
+            .field("params", &self.params)
+            .field("extra_params", &self.extra_params)
+            .finish()
+    }
+}
+
 #[cfg(feature = "hyper")]
 impl<B: Body<Data = Bytes>> TwitterStream<B> {
     /// A shorthand for `Builder::filter().listen()`.
@@ -358,11 +640,7 @@ impl<B: Body<Data = Bytes>> TwitterStream<B> {
     /// # Panics
     ///
     /// This will panic if the underlying HTTPS connector failed to initialize.
-    pub fn filter<C, A>(token: Token<C, A>) -> crate::hyper::FutureTwitterStream
-    where
-        C: Borrow<str>,
-        A: Borrow<str>,
-    {
+    pub fn filter<T: Authorizer>(token: T) -> crate::hyper::FutureTwitterStream {
         Builder::filter(token).listen()
     }
 
@@ -371,79 +649,324 @@ impl<B: Body<Data = Bytes>> TwitterStream<B> {
     /// # Panics
     ///
     /// This will panic if the underlying HTTPS connector failed to initialize.
-    pub fn sample<C, A>(token: Token<C, A>) -> crate::hyper::FutureTwitterStream
-    where
-        C: Borrow<str>,
-        A: Borrow<str>,
-    {
+    pub fn sample<T: Authorizer>(token: T) -> crate::hyper::FutureTwitterStream {
         Builder::sample(token).listen()
     }
 }
 
-impl<F, B, E> Future for FutureTwitterStream<F>
+impl<F, R> FutureTwitterStream<F, R> {
+    /// Returns a future that resolves to `Error::Custom(error)` without sending a request.
+    pub(crate) fn error(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        FutureTwitterStream {
+            response: None,
+            max_line_len: None,
+            sniff_gzip: false,
+            retry: None,
+            error: Some(error.into()),
+            authorizer: None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl<F> FutureTwitterStream<F> {
+    /// Returns a future that reads the stream from `response`, without a clock skew retry.
+    pub(crate) fn from_response(
+        response: F,
+        max_line_len: Option<usize>,
+        sniff_gzip: bool,
+    ) -> Self {
+        FutureTwitterStream {
+            response: Some(response),
+            max_line_len,
+            sniff_gzip,
+            retry: None,
+            error: None,
+            authorizer: None,
+        }
+    }
+}
+
+impl<F, R, B, E> Future for FutureTwitterStream<F, R>
 where
     F: Future<Output = Result<Response<B>, E>>,
+    R: Reconnect<F>,
     B: Body<Data = Bytes>,
 {
     type Output = Result<TwitterStream<B>, Error<E>>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let res = match ready!(self.project().response.poll(cx)) {
-            Ok(res) => res,
-            Err(e) => return Poll::Ready(Err(Error::Service(e))),
+        let mut this = self.project();
+        let (status, headers, body) = loop {
+            let response = match this.response.as_mut().as_pin_mut() {
+                Some(response) => response,
+                None => {
+                    let e = this.error.take().expect("polled after completion");
+                    return Poll::Ready(Err(Error::Custom(e)));
+                }
+            };
+            let res = match ready!(response.poll(cx)) {
+                Ok(res) => res,
+                Err(e) => return Poll::Ready(Err(Error::Service(e))),
+            };
+            let (parts, body) = res.into_parts();
+            let Parts {
+                status, headers, ..
+            } = parts;
+
+            if StatusCode::UNAUTHORIZED == status {
+                let retry = this.retry.take();
+                if let Some(response) = retry.and_then(|mut r| r.reconnect(&headers)) {
+                    this.response.set(Some(response));
+                    continue;
+                }
+            }
+            break (status, headers, body);
         };
-        let (parts, body) = res.into_parts();
-        let Parts {
-            status, headers, ..
-        } = parts;
+
+        if let Some(ref authorizer) = *this.authorizer {
+            authorizer.report(if StatusCode::OK == status {
+                ConnectionEvent::Connected
+            } else {
+                ConnectionEvent::Rejected(status)
+            });
+        }
 
         if StatusCode::OK != status {
             return Poll::Ready(Err(Error::Http(status)));
         }
 
-        let use_gzip = headers
-            .get_all(CONTENT_ENCODING)
-            .iter()
-            .any(|e| e == "gzip");
-        let inner = if use_gzip {
-            Lines::new(gzip::gzip(HttpBodyAsStream(body)))
-        } else {
-            Lines::new(gzip::identity(HttpBodyAsStream(body)))
+        let body = match Decoder::new(&headers, *this.sniff_gzip, HttpBodyAsStream(body)) {
+            Ok(body) => body,
+            Err(e) => return Poll::Ready(Err(Error::Decode(e))),
+        };
+        let inner = match *this.max_line_len {
+            Some(limit) => Lines::with_limit(body, limit),
+            None => Lines::new(body),
         };
 
-        Poll::Ready(Ok(TwitterStream { inner }))
+        Poll::Ready(Ok(TwitterStream {
+            inner,
+            offset: 0,
+            authorizer: this.authorizer.take(),
+        }))
     }
 }
 
-impl<B> Stream for TwitterStream<B>
-where
-    B: Body<Data = Bytes>,
-{
-    type Item = Result<string::String<Bytes>, Error<B::Error>>;
+impl<B: Body<Data = Bytes>> TwitterStream<B> {
+    /// Make the stream skip malformed lines instead of yielding an error for them.
+    ///
+    /// Each skipped line is passed to `on_malformed`, along with its byte offset
+    /// in the (decompressed) response body. Errors from the underlying connection
+    /// are still yielded by the stream.
+    pub fn tolerant<F>(self, on_malformed: F) -> Tolerant<B, F>
+    where
+        F: FnMut(MalformedLine),
+    {
+        Tolerant::new(self, on_malformed)
+    }
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    /// Make the stream yield lines as raw bytes, skipping the UTF-8 validation.
+    pub fn into_raw(self) -> RawTwitterStream<B> {
+        RawTwitterStream::new(self)
+    }
+
+    /// Poll the next non-blank line along with its byte offset in the body,
+    /// yielding `Ok(Err(_))` for a line that exceeds `max_line_len`.
+    #[allow(clippy::type_complexity)]
+    fn poll_raw_line(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Result<(u64, Bytes), MalformedLine>, Error<B::Error>>>> {
         let mut this = self.project();
 
         loop {
-            let line = ready_some!(this.inner.as_mut().poll_next(cx))?;
+            let offset = *this.offset;
+            let line = match ready_some!(this.inner.as_mut().poll_next(cx)) {
+                Ok(line) => line,
+                Err(Error::LineTooLong(len)) => {
+                    *this.offset += len as u64 + 2;
+                    let kind = Malformed::TooLong;
+                    let malformed = MalformedLine { offset, len, kind };
+                    return Poll::Ready(Some(Ok(Err(malformed))));
+                }
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            };
+            // The last line of a body may lack the CRLF, but nothing follows it anyway.
+            *this.offset += line.len() as u64 + 2;
+
             if line.iter().all(|&c| is_json_whitespace(c)) {
                 continue;
             }
 
-            str::from_utf8(&line).map_err(Error::Utf8)?;
-            let line = unsafe {
-                // Safety:
-                // - We have checked above that `line` is valid as UTF-8.
-                // - `Bytes` satisfies the requirements of `string::StableAsRef` trait
-                // (https://github.com/carllerche/string/pull/17)
-                string::String::<Bytes>::from_utf8_unchecked(line)
-            };
-            return Poll::Ready(Some(Ok(line)));
+            if let Some(ref authorizer) = *this.authorizer {
+                if let Some(code) = disconnect_code(&line) {
+                    authorizer.report(ConnectionEvent::Disconnected(code));
+                }
+            }
+
+            return Poll::Ready(Some(Ok(Ok((offset, line)))));
+        }
+    }
+
+    /// Poll the next non-blank line, yielding `Ok(Err(_))` for a line that is malformed.
+    #[allow(clippy::type_complexity)]
+    fn poll_line(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Result<string::String<Bytes>, MalformedLine>, Error<B::Error>>>> {
+        let (offset, line) = match ready_some!(self.poll_raw_line(cx))? {
+            Ok(line) => line,
+            Err(malformed) => return Poll::Ready(Some(Ok(Err(malformed)))),
+        };
+
+        if let Err(e) = str::from_utf8(&line) {
+            let len = line.len();
+            let kind = Malformed::Utf8(line, e);
+            return Poll::Ready(Some(Ok(Err(MalformedLine { offset, len, kind }))));
         }
+        let line = unsafe {
+            // Safety:
+            // - We have checked above that `line` is valid as UTF-8.
+            // - `Bytes` satisfies the requirements of `string::StableAsRef` trait
+            // (https://github.com/carllerche/string/pull/17)
+            string::String::<Bytes>::from_utf8_unchecked(line)
+        };
+        Poll::Ready(Some(Ok(Ok(line))))
+    }
+}
+
+impl<B> Stream for TwitterStream<B>
+where
+    B: Body<Data = Bytes>,
+{
+    type Item = Result<string::String<Bytes>, Error<B::Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let line = ready_some!(self.poll_line(cx))?;
+        Poll::Ready(Some(line.map_err(MalformedLine::into_error)))
+    }
+}
+
+/// Returns the `code` of a `disconnect` message.
+fn disconnect_code(line: &[u8]) -> Option<u64> {
+    if !line.starts_with(b"{\"disconnect\"") {
+        return None;
     }
+    let msg: json::Value = json::from_slice(line).ok()?;
+    msg["disconnect"]["code"].as_u64()
 }
 
 fn is_json_whitespace(c: u8) -> bool {
     // RFC7159 §2
     b" \t\n\r".contains(&c)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures_executor::block_on;
+    use http::header::{AUTHORIZATION, USER_AGENT};
+
+    use crate::auth::Deterministic;
+
+    #[test]
+    fn build_request() {
+        let auth = Deterministic::new(Token::new("ck", "cs", "ak", "as"), "nonce", 1);
+        let mut builder = Builder::filter(&auth);
+        builder.track("Rust").follow(&[12, 13][..]);
+
+        let req = builder.build_request();
+        assert_eq!(req.method(), RequestMethod::POST);
+        assert_eq!(
+            req.uri(),
+            "https://stream.twitter.com/1.1/statuses/filter.json"
+        );
+        assert_eq!(req.body(), b"follow=12%2C13&track=Rust");
+        assert_eq!(
+            req.headers()[AUTHORIZATION],
+            builder.signature_base().unwrap().authorization,
+        );
+
+        let mut sent = None;
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            sent = Some(req);
+            respond(StatusCode::OK, "")
+        });
+        block_on(builder.listen_with_client(client)).unwrap();
+        let sent = sent.unwrap();
+        assert_eq!(sent.uri(), req.uri());
+        assert_eq!(sent.headers(), req.headers());
+        assert_eq!(sent.body(), req.body());
+    }
+
+    #[test]
+    fn extra_headers_and_params() {
+        let auth = Deterministic::new(Token::new("ck", "cs", "ak", "as"), "nonce", 1);
+        let mut builder = Builder::sample(&auth);
+        builder
+            .header(USER_AGENT, HeaderValue::from_static("my-app/1.0"))
+            .header(AUTHORIZATION, HeaderValue::from_static("ignored"))
+            .param("tweet_mode", "extended")
+            .param("with", "a b");
+
+        let req = builder.build_request();
+        assert_eq!(
+            req.uri(),
+            "https://stream.twitter.com/1.1/statuses/sample.json?tweet_mode=extended&with=a%20b"
+        );
+        assert_eq!(req.headers()[USER_AGENT], "my-app/1.0");
+        let base = builder.signature_base().unwrap();
+        assert_eq!(req.headers().get_all(AUTHORIZATION).iter().count(), 1);
+        assert_eq!(req.headers()[AUTHORIZATION], base.authorization);
+        assert!(base
+            .normalized_parameters
+            .ends_with("&tweet_mode=extended&with=a%20b"));
+
+        let mut builder = Builder::filter(&auth);
+        builder.track("Rust").param("delimited", "length");
+        assert_eq!(
+            builder.build_request().body(),
+            b"track=Rust&delimited=length"
+        );
+
+        // A key set by both a dedicated method and `param` is sent twice.
+        builder.param("track", "Go");
+        assert_eq!(
+            builder.build_request().body(),
+            b"track=Rust&delimited=length&track=Go"
+        );
+    }
+
+    #[test]
+    fn custom_params() {
+        #[derive(oauth::Authorize)]
+        struct Params<'a> {
+            follow: &'a str,
+            with: Option<&'a str>,
+        }
+
+        let auth = Deterministic::new(Token::new("ck", "cs", "ak", "as"), "nonce", 1);
+        let endpoint = Uri::from_static("https://sitestream.twitter.com/1.1/site.json");
+        let mut builder =
+            Builder::custom(RequestMethod::GET, endpoint, &auth).with_params(Params {
+                follow: "12,13",
+                with: None,
+            });
+        builder.params_mut().with = Some("followings");
+        builder.param("stringify_friend_ids", "true");
+
+        let req = builder.build_request();
+        assert_eq!(
+            req.uri(),
+            "https://sitestream.twitter.com/1.1/site.json\
+             ?follow=12%2C13&with=followings&stringify_friend_ids=true"
+        );
+        let base = builder.signature_base().unwrap();
+        assert_eq!(req.headers()[AUTHORIZATION], base.authorization);
+        assert!(base
+            .normalized_parameters
+            .starts_with("follow=12%2C13&oauth_"));
+    }
+}
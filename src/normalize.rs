@@ -0,0 +1,331 @@
+//! A common model for Tweets from the v1.1 and v2 endpoints.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use twitter_stream::normalize::Tweet;
+//!
+//! let v1 = r#"{
+//!     "id_str": "1",
+//!     "text": "hello",
+//!     "user": {"id_str": "2", "name": "A", "screen_name": "a"}
+//! }"#;
+//! let tweet = Tweet::from_v1(&json::from_str(v1).unwrap()).unwrap();
+//!
+//! let v2 = r#"{
+//!     "data": {"id": "1", "text": "hello", "author_id": "2"},
+//!     "includes": {"users": [{"id": "2", "name": "A", "username": "a"}]}
+//! }"#;
+//! let msg: twitter_stream::v2::StreamMessage = json::from_str(v2).unwrap();
+//! assert_eq!(Tweet::from_v2(&msg).unwrap().author, tweet.author);
+//! ```
+
+use json::Value;
+
+use crate::v2::{ExpandedTweet, ReferenceType, StreamMessage};
+
+/// A Tweet in a form that does not depend on the API version it came from.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tweet {
+    pub id: String,
+    /// The full text of the Tweet, from `extended_tweet` for v1.1 Tweets.
+    ///
+    /// For a Retweet, this is the (possibly truncated) text of the Retweet itself,
+    /// as returned by both APIs. The original text is in `retweeted`.
+    pub text: String,
+    /// The creation time in the v2 format, e.g. `2020-10-10T20:19:24.000Z`.
+    pub created_at: Option<String>,
+    pub lang: Option<String>,
+    pub author: Option<User>,
+    /// The hashtags without the leading `#`.
+    pub hashtags: Vec<String>,
+    /// The usernames of the mentioned users without the leading `@`.
+    pub mentions: Vec<String>,
+    /// The expanded URLs.
+    pub urls: Vec<String>,
+    pub media: Vec<Media>,
+    pub place: Option<Place>,
+    /// The ID of the Tweet that this Tweet replies to.
+    pub in_reply_to: Option<String>,
+    /// The Retweeted Tweet, if the Tweet is a Retweet.
+    pub retweeted: Option<Box<Tweet>>,
+    /// The quoted Tweet, if any.
+    pub quoted: Option<Box<Tweet>>,
+    /// The tags (or IDs if untagged) of the v2 filtered stream rules that the Tweet matched.
+    pub matching_rules: Vec<String>,
+}
+
+/// The author of a `Tweet`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct User {
+    pub id: String,
+    pub username: String,
+    pub name: Option<String>,
+}
+
+/// A media attached to a `Tweet`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Media {
+    /// The numeric ID of the media, i.e. the v1.1 `id_str`, which is the v2 `media_key`
+    /// without the type prefix (e.g. `1300000000000000004` for `3_1300000000000000004`).
+    pub id: String,
+    /// The type of the media, e.g. `photo`, `animated_gif` or `video`.
+    pub kind: String,
+    pub url: Option<String>,
+}
+
+/// The place associated with a `Tweet`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Place {
+    pub id: String,
+    pub full_name: String,
+}
+
+impl Tweet {
+    /// Convert a v1.1 Tweet object.
+    ///
+    /// Returns `None` if `tweet` is not a Tweet, e.g. a `delete` or `limit` notice.
+    pub fn from_v1(tweet: &Value) -> Option<Self> {
+        let id = tweet["id_str"].as_str()?.to_owned();
+        let extended = tweet.get("extended_tweet");
+        let text = extended
+            .and_then(|e| e["full_text"].as_str())
+            .or_else(|| tweet["full_text"].as_str())
+            .or_else(|| tweet["text"].as_str())?
+            .to_owned();
+
+        let entities = extended
+            .and_then(|e| e.get("entities"))
+            .or_else(|| tweet.get("entities"));
+        let entities = |key: &str, field: &str| -> Vec<String> {
+            entities
+                .and_then(|e| e[key].as_array())
+                .map_or(&[][..], |a| a)
+                .iter()
+                .filter_map(|entity| entity[field].as_str())
+                .map(str::to_owned)
+                .collect()
+        };
+
+        let media = extended
+            .and_then(|e| e.get("extended_entities"))
+            .or_else(|| tweet.get("extended_entities"))
+            .and_then(|e| e["media"].as_array())
+            .map_or(&[][..], |a| a)
+            .iter()
+            .filter_map(|m| {
+                Some(Media {
+                    id: m["id_str"].as_str()?.to_owned(),
+                    kind: m["type"].as_str()?.to_owned(),
+                    url: string(&m["media_url_https"]),
+                })
+            })
+            .collect();
+
+        let user = &tweet["user"];
+        let author = user["id_str"].as_str().map(|id| User {
+            id: id.to_owned(),
+            username: user["screen_name"].as_str().unwrap_or_default().to_owned(),
+            name: string(&user["name"]),
+        });
+
+        let place = &tweet["place"];
+        let place = place["id"].as_str().map(|id| Place {
+            id: id.to_owned(),
+            full_name: place["full_name"].as_str().unwrap_or_default().to_owned(),
+        });
+
+        Some(Tweet {
+            id,
+            text,
+            created_at: tweet["created_at"].as_str().map(v1_timestamp),
+            lang: string(&tweet["lang"]),
+            author,
+            hashtags: entities("hashtags", "text"),
+            mentions: entities("user_mentions", "screen_name"),
+            urls: entities("urls", "expanded_url"),
+            media,
+            place,
+            in_reply_to: string(&tweet["in_reply_to_status_id_str"]),
+            retweeted: tweet
+                .get("retweeted_status")
+                .and_then(Tweet::from_v1)
+                .map(Box::new),
+            quoted: tweet
+                .get("quoted_status")
+                .and_then(Tweet::from_v1)
+                .map(Box::new),
+            matching_rules: Vec::new(),
+        })
+    }
+
+    /// Convert the Tweet of a v2 stream message, resolving its `includes`.
+    ///
+    /// Returns `None` if the message has no Tweet.
+    pub fn from_v2(msg: &StreamMessage) -> Option<Self> {
+        msg.expand().map(|tweet| Tweet::from(&tweet))
+    }
+}
+
+impl<'a> From<&ExpandedTweet<'a>> for Tweet {
+    fn from(expanded: &ExpandedTweet<'a>) -> Self {
+        let tweet = expanded.tweet;
+        let entities = |key: &str, field: &str| -> Vec<String> {
+            tweet
+                .extra
+                .get("entities")
+                .and_then(|e| e[key].as_array())
+                .map_or(&[][..], |a| a)
+                .iter()
+                .filter_map(|entity| entity[field].as_str())
+                .map(str::to_owned)
+                .collect()
+        };
+        let referenced =
+            |kind: ReferenceType| expanded.referenced_tweets.iter().find(|r| *r.kind == kind);
+
+        Tweet {
+            id: tweet.id.clone(),
+            text: tweet.text.clone(),
+            created_at: tweet.created_at.clone(),
+            lang: tweet.lang.clone(),
+            author: expanded.author.map(|user| User {
+                id: user.id.clone(),
+                username: user.username.clone(),
+                name: Some(user.name.clone()),
+            }),
+            hashtags: entities("hashtags", "tag"),
+            mentions: entities("mentions", "username"),
+            urls: entities("urls", "expanded_url"),
+            media: expanded
+                .media
+                .iter()
+                .map(|m| Media {
+                    id: media_id(&m.media_key).to_owned(),
+                    kind: m.kind.clone(),
+                    url: m.url.clone(),
+                })
+                .collect(),
+            place: expanded.place.map(|place| Place {
+                id: place.id.clone(),
+                full_name: place.full_name.clone(),
+            }),
+            in_reply_to: referenced(ReferenceType::RepliedTo).map(|r| r.id.to_owned()),
+            retweeted: referenced(ReferenceType::Retweeted)
+                .and_then(|r| r.tweet.as_ref())
+                .map(|t| Box::new(Tweet::from(t))),
+            quoted: referenced(ReferenceType::Quoted)
+                .and_then(|r| r.tweet.as_ref())
+                .map(|t| Box::new(Tweet::from(t))),
+            matching_rules: expanded
+                .matching_rules
+                .iter()
+                .map(|rule| rule.tag.as_ref().unwrap_or(&rule.id).clone())
+                .collect(),
+        }
+    }
+}
+
+/// Strip the type prefix from a v2 `media_key` like `3_1300000000000000004`.
+fn media_id(media_key: &str) -> &str {
+    media_key
+        .find('_')
+        .map_or(media_key, |i| &media_key[i + 1..])
+}
+
+fn string(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_owned)
+}
+
+/// Convert a v1.1 timestamp like `Wed Oct 10 20:19:24 +0000 2018` to the v2 format,
+/// returning it as is if it is not in the expected form.
+fn v1_timestamp(s: &str) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let parts: Vec<&str> = s.split_whitespace().collect();
+    if let [_, month, day, time, "+0000", year] = *parts {
+        if let Some(month) = MONTHS.iter().position(|&m| m == month) {
+            if day.len() == 2 && time.len() == 8 && year.len() == 4 {
+                return format!("{}-{:02}-{}T{}.000Z", year, month + 1, day, time);
+            }
+        }
+    }
+    s.to_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const V1_RETWEET: &str = include_str!("../tests/fixtures/v1_retweet.json");
+    const V2_RETWEET: &str = include_str!("../tests/fixtures/v2_retweet.json");
+
+    fn v1() -> Tweet {
+        Tweet::from_v1(&json::from_str(V1_RETWEET).unwrap()).unwrap()
+    }
+
+    fn v2() -> Tweet {
+        Tweet::from_v2(&json::from_str(V2_RETWEET).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn v1_tweet() {
+        let tweet = v1();
+        assert_eq!(tweet.id, "1300000000000000003");
+        assert_eq!(
+            tweet.created_at.as_deref(),
+            Some("2020-08-30T12:00:03.000Z")
+        );
+        assert_eq!(tweet.author.as_ref().unwrap().username, "bob");
+        assert!(tweet.quoted.is_none());
+
+        let retweeted = tweet.retweeted.as_ref().unwrap();
+        assert!(retweeted
+            .text
+            .ends_with("the whole text, not truncated https://t.co/abc"));
+        assert_eq!(retweeted.hashtags, ["cats"]);
+        assert_eq!(retweeted.mentions, ["carol"]);
+        assert_eq!(retweeted.urls, ["https://example.com/cats"]);
+        assert_eq!(retweeted.media[0].kind, "photo");
+        assert_eq!(retweeted.place.as_ref().unwrap().full_name, "Tokyo, Japan");
+
+        let quoted = retweeted.quoted.as_ref().unwrap();
+        assert_eq!(quoted.id, "1300000000000000001");
+        assert_eq!(quoted.text, "Cats are great");
+
+        assert!(Tweet::from_v1(&json::json!({"delete": {"status": {"id_str": "1"}}})).is_none());
+    }
+
+    #[test]
+    fn v2_tweet() {
+        let tweet = v2();
+        assert_eq!(tweet.matching_rules, ["cats"]);
+        let retweeted = tweet.retweeted.as_ref().unwrap();
+        assert_eq!(retweeted.media[0].id, "1300000000000000004");
+        assert_eq!(retweeted.author.as_ref().unwrap().username, "alice");
+    }
+
+    #[test]
+    fn same_model() {
+        let mut v1 = v1();
+        let mut v2 = v2();
+
+        // Only available in the v2 format.
+        v2.matching_rules.clear();
+        // v2 does not expand the Tweets referenced by included Tweets.
+        v1.retweeted.as_mut().unwrap().quoted = None;
+
+        assert_eq!(v1, v2);
+    }
+
+    #[test]
+    fn timestamp() {
+        assert_eq!(
+            v1_timestamp("Wed Oct 10 20:19:24 +0000 2018"),
+            "2018-10-10T20:19:24.000Z"
+        );
+        assert_eq!(v1_timestamp("yesterday"), "yesterday");
+    }
+}
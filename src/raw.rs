@@ -0,0 +1,80 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures_core::Stream;
+use http_body::Body;
+use pin_project::pin_project;
+
+use crate::error::Error;
+use crate::{MalformedLine, TwitterStream};
+
+/// A listener for Twitter Streaming API that yields raw lines without validating them as UTF-8.
+///
+/// Like `TwitterStream`, the CRLF delimiters and blank (keep-alive) lines are stripped.
+/// This is created by the `TwitterStream::into_raw` method.
+#[pin_project]
+pub struct RawTwitterStream<B: Body<Data = Bytes>> {
+    #[pin]
+    inner: TwitterStream<B>,
+}
+
+impl<B: Body<Data = Bytes>> RawTwitterStream<B> {
+    pub(crate) fn new(inner: TwitterStream<B>) -> Self {
+        RawTwitterStream { inner }
+    }
+
+    /// Convert `self` back into a `TwitterStream` that validates the lines as UTF-8.
+    pub fn into_validated(self) -> TwitterStream<B> {
+        self.inner
+    }
+}
+
+impl<B: Body<Data = Bytes>> From<RawTwitterStream<B>> for TwitterStream<B> {
+    fn from(raw: RawTwitterStream<B>) -> Self {
+        raw.into_validated()
+    }
+}
+
+impl<B> Stream for RawTwitterStream<B>
+where
+    B: Body<Data = Bytes>,
+{
+    type Item = Result<Bytes, Error<B::Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let line = ready_some!(self.project().inner.poll_raw_line(cx))?;
+        let line = line.map(|(_, line)| line);
+        Poll::Ready(Some(line.map_err(MalformedLine::into_error)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures_executor::{block_on, block_on_stream};
+    use futures_util::future;
+    use futures_util::stream::StreamExt;
+    use http::Response;
+
+    use crate::util::ChunksBody;
+    use crate::FutureTwitterStream;
+
+    #[test]
+    fn raw_then_validated() {
+        let body: [&'static [u8]; 3] = [b"{\"a\":1}\r\n\r\n\xFF\r", b"\n{\"b\"", b":2}\r\n"];
+        let body = ChunksBody(body.iter().map(|&c| Bytes::from_static(c)));
+        let response = future::ok::<_, ()>(Response::new(body));
+        let stream = FutureTwitterStream::from_response(response, None, false);
+        let mut raw = block_on(stream).unwrap().into_raw();
+
+        assert_eq!(&block_on(raw.next()).unwrap().unwrap()[..], b"{\"a\":1}");
+        assert_eq!(&block_on(raw.next()).unwrap().unwrap()[..], b"\xFF");
+
+        let lines = block_on_stream(raw.into_validated())
+            .map(|line| line.unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(lines, ["{\"b\":2}"]);
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt::{self, Debug, Formatter};
+use std::marker::PhantomData;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use http::header::{HeaderMap, DATE};
+use http::Request;
+use tower_service::Service;
+
+use crate::auth::Authorizer;
+use crate::types::{RequestMethod, Uri};
+
+/// The maximum difference in seconds between the `oauth_timestamp` and the server clock
+/// that is assumed not to be the cause of a `401 Unauthorized` response.
+const MAX_SKEW: i64 = 30;
+
+/// The state for connecting again after the server has rejected a request because of
+/// a skew between the local and the server clocks.
+///
+/// When a request signed with a timestamp (see `Authorizer::clock_dependent`) is rejected
+/// with `401 Unauthorized`, the clock offset is computed from the `Date` header of
+/// the response and the request is retried once with corrected timestamps.
+pub struct ClockSkewRetry<S, ReqB> {
+    client: S,
+    authorizer: Box<dyn Authorizer + Send + Sync>,
+    method: RequestMethod,
+    endpoint: Uri,
+    params: String,
+    headers: HeaderMap,
+    clock_offset: i64,
+    marker: PhantomData<fn() -> ReqB>,
+}
+
+mod private {
+    use http::header::HeaderMap;
+
+    pub trait Reconnect<F> {
+        /// Returns the retried response if the rejected response (with `headers`) was caused by
+        /// a clock skew.
+        fn reconnect(&mut self, headers: &HeaderMap) -> Option<F>;
+    }
+}
+
+pub(crate) use self::private::Reconnect;
+
+impl<S, ReqB> ClockSkewRetry<S, ReqB> {
+    pub(crate) fn new(
+        client: S,
+        authorizer: Box<dyn Authorizer + Send + Sync>,
+        method: RequestMethod,
+        endpoint: Uri,
+        params: String,
+        headers: HeaderMap,
+        clock_offset: i64,
+    ) -> Self {
+        ClockSkewRetry {
+            client,
+            authorizer,
+            method,
+            endpoint,
+            params,
+            headers,
+            clock_offset,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<F> Reconnect<F> for () {
+    fn reconnect(&mut self, _: &HeaderMap) -> Option<F> {
+        None
+    }
+}
+
+impl<S, ReqB> Reconnect<S::Future> for ClockSkewRetry<S, ReqB>
+where
+    S: Service<Request<ReqB>>,
+    ReqB: Default + From<Vec<u8>>,
+{
+    fn reconnect(&mut self, headers: &HeaderMap) -> Option<S::Future> {
+        let clock_offset = server_clock_offset(headers)?;
+        if (clock_offset - self.clock_offset).abs() <= MAX_SKEW {
+            return None;
+        }
+
+        let authorization = self.authorizer.authorize_with_clock_offset(
+            &self.method,
+            &self.endpoint,
+            &self.params,
+            clock_offset,
+        );
+        let mut headers = self.headers.clone();
+        headers.extend(authorization);
+        let req = crate::make_request(&self.method, &self.endpoint, &self.params, headers);
+        Some(self.client.call(req))
+    }
+}
+
+/// Returns the number of seconds by which the server clock is ahead of the local clock,
+/// according to the `Date` header.
+fn server_clock_offset(headers: &HeaderMap) -> Option<i64> {
+    let date = headers.get(DATE)?.to_str().ok()?;
+    let date = httpdate::parse_http_date(date).ok()?;
+    let secs = |t: SystemTime| match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    Some(secs(date) - secs(SystemTime::now()))
+}
+
+impl<S: Debug, ReqB> Debug for ClockSkewRetry<S, ReqB> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClockSkewRetry")
+            .field("client", &self.client)
+            .field("method", &self.method)
+            .field("endpoint", &self.endpoint)
+            .field("clock_offset", &self.clock_offset)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    use futures_executor::block_on;
+    use http::header::{HeaderValue, AUTHORIZATION};
+
+    use crate::auth::BearerToken;
+    use crate::types::StatusCode;
+    use crate::util::{respond, ServiceFn};
+    use crate::Token;
+    use crate::{Builder, Error};
+
+    fn timestamp<B>(req: &Request<B>) -> i64 {
+        let authorization = req.headers()[AUTHORIZATION].to_str().unwrap();
+        let value = authorization
+            .split(',')
+            .find_map(|param| param.trim().strip_prefix("oauth_timestamp=\""))
+            .unwrap();
+        value.trim_end_matches('"').parse().unwrap()
+    }
+
+    fn now() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+    }
+
+    fn unauthorized(date: SystemTime) -> http::Response<impl http_body::Body<Data = bytes::Bytes>> {
+        let mut res = respond(StatusCode::UNAUTHORIZED, "");
+        let date = httpdate::fmt_http_date(date);
+        res.headers_mut()
+            .insert(DATE, HeaderValue::from_str(&date).unwrap());
+        res
+    }
+
+    #[test]
+    fn retry_with_server_clock() {
+        let mut timestamps = Vec::new();
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            timestamps.push(timestamp(&req));
+            if timestamps.len() == 1 {
+                unauthorized(SystemTime::now() + Duration::from_secs(3600))
+            } else {
+                let mut res = unauthorized(SystemTime::now());
+                *res.status_mut() = StatusCode::OK;
+                res
+            }
+        });
+
+        let token = Token::new("ck", "cs", "ak", "as");
+        block_on(Builder::sample(token).listen_with_client(client)).unwrap();
+
+        assert_eq!(timestamps.len(), 2);
+        let now = now();
+        assert!((timestamps[0] - now).abs() <= 5);
+        assert!((timestamps[1] - (now + 3600)).abs() <= 5);
+    }
+
+    #[test]
+    fn no_retry_without_skew() {
+        let mut count = 0;
+        let client = ServiceFn(|_: Request<Vec<u8>>| {
+            count += 1;
+            unauthorized(SystemTime::now())
+        });
+
+        let token = Token::new("ck", "cs", "ak", "as");
+        match block_on(Builder::sample(token).listen_with_client(client)) {
+            Err(Error::Http(StatusCode::UNAUTHORIZED)) => {}
+            res => panic!("expected 401, got {:?}", res.map(|_| ())),
+        }
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn no_retry_for_bearer_token() {
+        let mut count = 0;
+        let client = ServiceFn(|_: Request<Vec<u8>>| {
+            count += 1;
+            unauthorized(SystemTime::now() + Duration::from_secs(3600))
+        });
+
+        let token = BearerToken::new("AAAA");
+        assert!(block_on(Builder::sample(token).listen_with_client(client)).is_err());
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn configured_clock_offset() {
+        let mut ts = None;
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            ts = Some(timestamp(&req));
+            respond(StatusCode::OK, "")
+        });
+
+        let token = Token::new("ck", "cs", "ak", "as");
+        block_on(
+            Builder::sample(token)
+                .clock_offset(-600)
+                .listen_with_client(client),
+        )
+        .unwrap();
+
+        assert!((ts.unwrap() - (now() - 600)).abs() <= 5);
+    }
+}
@@ -1,9 +1,24 @@
 use std::borrow::Borrow;
+use std::fmt::{self, Debug, Formatter};
 
 use oauth::Credentials;
+#[cfg(feature = "serde")]
+use serde::de::{Deserialize, Deserializer};
+#[cfg(feature = "serde")]
+use serde::ser::{Serialize, Serializer};
+
+#[cfg(feature = "serde")]
+use crate::config;
+use crate::util::Hidden;
 
 /// An OAuth token used to log into Twitter.
-#[derive(Copy, Clone, Debug)]
+///
+/// The `Debug` output only shows the identifiers, leaving out the secrets.
+///
+/// With the `serde` feature, the token is (de)serialized as a map with `consumer_key`, `consumer_secret`, `access_key`
+/// and `access_secret` entries. The latter two may also be named `access_token` and
+/// `access_token_secret` respectively.
+#[derive(Copy, Clone)]
 pub struct Token<C = String, T = String> {
     pub client: Credentials<C>,
     pub token: Credentials<T>,
@@ -30,3 +45,118 @@ impl<C: Borrow<str>, T: Borrow<str>> Token<C, T> {
         Token::from_credentials(self.client.as_ref(), self.token.as_ref())
     }
 }
+
+impl<C: Borrow<str>, T: Borrow<str>> Debug for Token<C, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Token")
+            .field("client_identifier", &self.client.identifier())
+            .field("client_secret", &Hidden)
+            .field("token_identifier", &self.token.identifier())
+            .field("token_secret", &Hidden)
+            .finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Token {
+    /// Read the token from the `TWITTER_CONSUMER_KEY`, `TWITTER_CONSUMER_SECRET`,
+    /// `TWITTER_ACCESS_TOKEN` and `TWITTER_ACCESS_TOKEN_SECRET` environment variables.
+    ///
+    /// This requires the `serde` feature.
+    pub fn from_env() -> Result<Self, config::Error> {
+        Ok(Token::new(
+            config::required_var("TWITTER_CONSUMER_KEY")?,
+            config::required_var("TWITTER_CONSUMER_SECRET")?,
+            config::required_var("TWITTER_ACCESS_TOKEN")?,
+            config::required_var("TWITTER_ACCESS_TOKEN_SECRET")?,
+        ))
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize, serde::Serialize)]
+struct TokenRepr<C, T> {
+    consumer_key: C,
+    consumer_secret: C,
+    #[serde(alias = "access_token")]
+    access_key: T,
+    #[serde(alias = "access_token_secret")]
+    access_secret: T,
+}
+
+#[cfg(feature = "serde")]
+impl<'de, C, T> Deserialize<'de> for Token<C, T>
+where
+    C: Borrow<str> + Deserialize<'de>,
+    T: Borrow<str> + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let repr = TokenRepr::deserialize(d)?;
+        Ok(Token::new(
+            repr.consumer_key,
+            repr.consumer_secret,
+            repr.access_key,
+            repr.access_secret,
+        ))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<C: Borrow<str>, T: Borrow<str>> Serialize for Token<C, T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        TokenRepr {
+            consumer_key: self.client.identifier(),
+            consumer_secret: self.client.secret(),
+            access_key: self.token.identifier(),
+            access_secret: self.token.secret(),
+        }
+        .serialize(s)
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::*;
+
+    use std::env;
+
+    #[test]
+    fn serde() {
+        let token: Token = json::from_str(
+            r#"{"consumer_key":"ck","consumer_secret":"cs","access_token":"ak","access_token_secret":"as"}"#,
+        )
+        .unwrap();
+        assert_eq!(token.client.identifier(), "ck");
+        assert_eq!(token.client.secret(), "cs");
+        assert_eq!(token.token.identifier(), "ak");
+        assert_eq!(token.token.secret(), "as");
+        assert_eq!(
+            json::to_string(&token.as_ref()).unwrap(),
+            r#"{"consumer_key":"ck","consumer_secret":"cs","access_key":"ak","access_secret":"as"}"#,
+        );
+    }
+
+    #[test]
+    fn from_env() {
+        env::set_var("TWITTER_CONSUMER_KEY", "ck");
+        env::set_var("TWITTER_CONSUMER_SECRET", "cs");
+        env::set_var("TWITTER_ACCESS_TOKEN", "ak");
+        let missing = Token::from_env();
+        env::set_var("TWITTER_ACCESS_TOKEN_SECRET", "as");
+        let token = Token::from_env();
+        for &name in &[
+            "TWITTER_CONSUMER_KEY",
+            "TWITTER_CONSUMER_SECRET",
+            "TWITTER_ACCESS_TOKEN",
+            "TWITTER_ACCESS_TOKEN_SECRET",
+        ] {
+            env::remove_var(name);
+        }
+
+        match missing {
+            Err(config::Error::MissingVar(name)) => assert_eq!(name, "TWITTER_ACCESS_TOKEN_SECRET"),
+            res => panic!("expected a missing variable error, got {:?}", res),
+        }
+        assert_eq!(token.unwrap().token.secret(), "as");
+    }
+}
@@ -0,0 +1,132 @@
+use std::pin::Pin;
+use std::str::Utf8Error;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures_core::Stream;
+use http_body::Body;
+use pin_project::pin_project;
+
+use crate::error::Error;
+use crate::TwitterStream;
+
+/// A `TwitterStream` that skips malformed lines instead of yielding an error.
+///
+/// This is created by the `TwitterStream::tolerant` method.
+#[pin_project]
+pub struct Tolerant<B: Body<Data = Bytes>, F> {
+    #[pin]
+    inner: TwitterStream<B>,
+    on_malformed: F,
+}
+
+/// A line that was skipped by a `Tolerant` stream.
+#[derive(Clone, Debug)]
+pub struct MalformedLine {
+    /// Byte offset of the start of the line in the (decompressed) response body.
+    pub offset: u64,
+    /// Length of the line in bytes, excluding the trailing CRLF.
+    pub len: usize,
+    /// The reason why the line was skipped.
+    pub kind: Malformed,
+}
+
+/// The reason why a line was considered malformed.
+#[derive(Clone, Debug)]
+pub enum Malformed {
+    /// The line is not valid as UTF-8. Contains the raw line.
+    Utf8(Bytes, Utf8Error),
+    /// The line is longer than the limit set with `Builder::max_line_len`.
+    TooLong,
+}
+
+impl<B: Body<Data = Bytes>, F> Tolerant<B, F> {
+    pub(crate) fn new(inner: TwitterStream<B>, on_malformed: F) -> Self {
+        Tolerant {
+            inner,
+            on_malformed,
+        }
+    }
+
+    /// Unwrap the underlying `TwitterStream`.
+    pub fn into_inner(self) -> TwitterStream<B> {
+        self.inner
+    }
+}
+
+impl<B, F> Stream for Tolerant<B, F>
+where
+    B: Body<Data = Bytes>,
+    F: FnMut(MalformedLine),
+{
+    type Item = Result<string::String<Bytes>, Error<B::Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        loop {
+            match ready_some!(this.inner.as_mut().poll_line(cx)) {
+                Ok(Ok(line)) => return Poll::Ready(Some(Ok(line))),
+                Ok(Err(malformed)) => (this.on_malformed)(malformed),
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+    }
+}
+
+impl MalformedLine {
+    /// Convert `self` into the error that a non-tolerant `TwitterStream` yields for the line.
+    pub fn into_error<E>(self) -> Error<E> {
+        match self.kind {
+            Malformed::Utf8(_, e) => Error::Utf8(e),
+            Malformed::TooLong => Error::LineTooLong(self.len),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::RefCell;
+
+    use futures_executor::{block_on, block_on_stream};
+    use futures_util::future;
+    use http::Response;
+
+    use crate::util::ChunksBody;
+    use crate::FutureTwitterStream;
+
+    #[test]
+    fn skip_malformed() {
+        let body: [&'static [u8]; 4] = [
+            b"{\"a\":1}\r\n\r\n",
+            b"\xFF\xFE\r\n{\"b\":2}\r\n",
+            b"{\"too\":\"long\"}\r\n",
+            b"{\"c\":3}\r\n",
+        ];
+        let body = ChunksBody(body.iter().map(|&c| Bytes::from_static(c)));
+        let response = future::ok::<_, ()>(Response::new(body));
+        let stream = FutureTwitterStream::from_response(response, Some(10), false);
+        let stream = block_on(stream).unwrap();
+
+        let malformed = RefCell::new(Vec::new());
+        let lines = block_on_stream(stream.tolerant(|m| malformed.borrow_mut().push(m)))
+            .map(|line| line.unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(lines, ["{\"a\":1}", "{\"b\":2}", "{\"c\":3}"]);
+
+        let malformed: Vec<MalformedLine> = malformed.into_inner();
+        assert_eq!(malformed.len(), 2);
+        assert_eq!((malformed[0].offset, malformed[0].len), (11, 2));
+        match malformed[0].kind {
+            Malformed::Utf8(ref line, _) => assert_eq!(&line[..], b"\xFF\xFE"),
+            ref kind => panic!("{:?}", kind),
+        }
+        assert_eq!((malformed[1].offset, malformed[1].len), (24, 14));
+        match malformed[1].kind {
+            Malformed::TooLong => {}
+            ref kind => panic!("{:?}", kind),
+        }
+    }
+}
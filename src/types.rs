@@ -1,5 +1,6 @@
 //! Common types used across the crate.
 
+pub use http::header::HeaderValue;
 pub use http::Method as RequestMethod;
 pub use http::StatusCode;
 pub use http::Uri;
@@ -7,6 +8,11 @@ pub use http::Uri;
 string_enums! {
     /// Represents the `filter_level` parameter in API requests.
     #[derive(Clone, Debug)]
+    #[cfg_attr(
+        feature = "serde",
+        derive(serde::Deserialize, serde::Serialize),
+        serde(from = "String", into = "String")
+    )]
     pub enum FilterLevel {
         None("none"),
         Low("low"),
@@ -20,3 +26,23 @@ impl std::default::Default for FilterLevel {
         FilterLevel::None
     }
 }
+
+impl From<String> for FilterLevel {
+    fn from(s: String) -> Self {
+        match &*s {
+            "none" => FilterLevel::None,
+            "low" => FilterLevel::Low,
+            "medium" => FilterLevel::Medium,
+            _ => FilterLevel::Custom(s),
+        }
+    }
+}
+
+impl From<FilterLevel> for String {
+    fn from(level: FilterLevel) -> Self {
+        match level {
+            FilterLevel::Custom(s) => s,
+            _ => level.as_ref().to_owned(),
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use std::fmt::{self, Display, Formatter};
+use std::fmt::{self, Debug, Display, Formatter};
+use std::future::Future;
 use std::mem;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -6,7 +7,9 @@ use std::task::{Context, Poll};
 use bytes::{Buf, Bytes, BytesMut};
 use futures_util::ready;
 use futures_util::stream::{Fuse, IntoStream, Stream, StreamExt, TryStream, TryStreamExt};
+use http::header::HeaderValue;
 use http_body::Body;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC};
 use pin_project::pin_project;
 
 use crate::error::Error;
@@ -97,6 +100,9 @@ pub struct Lines<S> {
     #[pin]
     stream: Fuse<IntoStream<S>>,
     buf: BytesMut,
+    limit: Option<usize>,
+    // Number of bytes of the current line that have been dropped for exceeding `limit`.
+    discarded: usize,
 }
 
 /// Wraps `http_body::Body` to make it a `Stream`.
@@ -108,6 +114,19 @@ impl<S: TryStream> Lines<S> {
         Lines {
             stream: stream.into_stream().fuse(),
             buf: BytesMut::new(),
+            limit: None,
+            discarded: 0,
+        }
+    }
+
+    /// Creates a `Lines` that yields `Error::LineTooLong` in place of lines
+    /// longer than `limit` bytes, without buffering their whole content.
+    pub fn with_limit(stream: S, limit: usize) -> Self {
+        Lines {
+            stream: stream.into_stream().fuse(),
+            buf: BytesMut::new(),
+            limit: Some(limit),
+            discarded: 0,
         }
     }
 }
@@ -119,7 +138,7 @@ impl<S: TryStream<Ok = Bytes, Error = Error<E>>, E> Stream for Lines<S> {
         let mut this = self.project();
 
         if let Some(line) = remove_first_line(&mut this.buf) {
-            return Poll::Ready(Some(Ok(line.freeze())));
+            return Poll::Ready(Some(check_len(line, this.limit, this.discarded)));
         }
 
         // Now `self.buf` does not have a CRLF.
@@ -134,9 +153,9 @@ impl<S: TryStream<Ok = Bytes, Error = Error<E>>, E> Stream for Lines<S> {
                         // `impl From<Bytes> for BytesMut` was removed in `bytes` 0.5.
                         break c[..].into();
                     }
-                } else if !this.buf.is_empty() {
-                    let ret = mem::replace(this.buf, BytesMut::new()).freeze();
-                    return Poll::Ready(Some(Ok(ret)));
+                } else if !this.buf.is_empty() || *this.discarded > 0 {
+                    let ret = mem::replace(this.buf, BytesMut::new());
+                    return Poll::Ready(Some(check_len(ret, this.limit, this.discarded)));
                 } else {
                     return Poll::Ready(None);
                 }
@@ -147,12 +166,24 @@ impl<S: TryStream<Ok = Bytes, Error = Error<E>>, E> Stream for Lines<S> {
                 chunk.advance(1);
                 let line_len = this.buf.len() - 1;
                 this.buf.truncate(line_len);
-                return Poll::Ready(Some(Ok(mem::replace(this.buf, chunk).freeze())));
+                let line = mem::replace(this.buf, chunk);
+                return Poll::Ready(Some(check_len(line, this.limit, this.discarded)));
             } else if let Some(line) = remove_first_line(&mut chunk) {
                 this.buf.unsplit(line);
-                return Poll::Ready(Some(Ok(mem::replace(this.buf, chunk).freeze())));
+                let line = mem::replace(this.buf, chunk);
+                return Poll::Ready(Some(check_len(line, this.limit, this.discarded)));
             } else {
                 this.buf.unsplit(chunk);
+                match *this.limit {
+                    Some(limit) if *this.discarded + this.buf.len() > limit => {
+                        // Keep the last byte since it may be the CR of a CRLF
+                        // whose LF is in the next chunk.
+                        let n = this.buf.len() - 1;
+                        this.buf.advance(n);
+                        *this.discarded += n;
+                    }
+                    _ => {}
+                }
             }
         }
     }
@@ -186,6 +217,10 @@ pub fn fmt_follow(ids: &[u64], f: &mut Formatter<'_>) -> fmt::Result {
     fmt_join(ids, COMMA, f)
 }
 
+pub fn fmt_list<T: Display>(t: &[T], f: &mut Formatter<'_>) -> fmt::Result {
+    fmt_join(t, ",", f)
+}
+
 type Location = ((f64, f64), (f64, f64));
 
 pub fn fmt_locations(locs: &[Location], f: &mut Formatter<'_>) -> fmt::Result {
@@ -209,6 +244,84 @@ pub fn not(p: &bool) -> bool {
     !p
 }
 
+/// Makes a value of `Authorization` header for HTTP Basic authentication.
+pub fn basic_auth(username: &str, password: &str) -> HeaderValue {
+    let credentials = base64::encode(format!("{}:{}", username, password));
+    let mut value = HeaderValue::from_str(&format!("Basic {}", credentials)).unwrap();
+    value.set_sensitive(true);
+    value
+}
+
+/// A placeholder for a secret in `Debug` output.
+pub struct Hidden;
+
+impl Debug for Hidden {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("<hidden>")
+    }
+}
+
+/// The characters to be percent-encoded in a request parameter, i.e. all but the unreserved
+/// characters of RFC 3986.
+pub const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Encode `params` as an `application/x-www-form-urlencoded` string.
+pub fn encode_params<A: oauth::Authorize>(params: &A) -> String {
+    // The signer is only used to serialize the parameters. The signature is discarded.
+    oauth::Builder::<_, &str>::new(oauth::Credentials::new("", ""), oauth::Plaintext)
+        .build_form("", "", params)
+        .data
+}
+
+/// Reads the whole content of `body`.
+pub fn read_body<B: Body>(body: B) -> impl Future<Output = Result<Vec<u8>, Error<B::Error>>> {
+    HttpBodyAsStream(body).try_fold(Vec::new(), |mut vec, mut chunk| {
+        while chunk.has_remaining() {
+            let n = chunk.bytes().len();
+            vec.extend_from_slice(chunk.bytes());
+            chunk.advance(n);
+        }
+        futures_util::future::ok(vec)
+    })
+}
+
+/// Reads the whole content of `body`, or returns `None` as soon as it exceeds `limit` bytes.
+pub async fn read_body_limited<B: Body>(
+    body: B,
+    limit: usize,
+) -> Result<Option<Vec<u8>>, Error<B::Error>> {
+    let body = HttpBodyAsStream(body);
+    futures_util::pin_mut!(body);
+    let mut vec = Vec::new();
+    while let Some(mut chunk) = body.try_next().await? {
+        if chunk.remaining() > limit - vec.len() {
+            return Ok(None);
+        }
+        while chunk.has_remaining() {
+            let n = chunk.bytes().len();
+            vec.extend_from_slice(chunk.bytes());
+            chunk.advance(n);
+        }
+    }
+    Ok(Some(vec))
+}
+
+fn check_len<E>(
+    line: BytesMut,
+    limit: &Option<usize>,
+    discarded: &mut usize,
+) -> Result<Bytes, Error<E>> {
+    let len = line.len() + mem::replace(discarded, 0);
+    match *limit {
+        Some(limit) if len > limit => Err(Error::LineTooLong(len)),
+        _ => Ok(line.freeze()),
+    }
+}
+
 fn remove_first_line(buf: &mut BytesMut) -> Option<BytesMut> {
     if buf.len() < 2 {
         return None;
@@ -225,6 +338,64 @@ fn remove_first_line(buf: &mut BytesMut) -> Option<BytesMut> {
     None
 }
 
+/// An `http_body::Body` that yields the chunks from an iterator, for use in tests.
+#[cfg(test)]
+pub struct ChunksBody<I>(pub I);
+
+#[cfg(test)]
+impl<I: Iterator<Item = Bytes> + Unpin> Body for ChunksBody<I> {
+    type Data = Bytes;
+    type Error = std::convert::Infallible;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        Poll::Ready(self.0.next().map(Ok))
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+}
+
+/// Makes a response with the given status and body, for use in tests.
+#[cfg(test)]
+pub fn respond(
+    status: http::StatusCode,
+    body: &'static str,
+) -> http::Response<ChunksBody<std::iter::Once<Bytes>>> {
+    let body = ChunksBody(std::iter::once(Bytes::from_static(body.as_bytes())));
+    let mut res = http::Response::new(body);
+    *res.status_mut() = status;
+    res
+}
+
+/// A `tower_service::Service` that responds to requests with a function, for use in tests.
+#[cfg(test)]
+pub struct ServiceFn<F>(pub F);
+
+#[cfg(test)]
+impl<F, ReqB, ResB> tower_service::Service<http::Request<ReqB>> for ServiceFn<F>
+where
+    F: FnMut(http::Request<ReqB>) -> http::Response<ResB>,
+{
+    type Response = http::Response<ResB>;
+    type Error = std::convert::Infallible;
+    type Future = futures_util::future::Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<ReqB>) -> Self::Future {
+        futures_util::future::ok((self.0)(req))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -256,4 +427,41 @@ mod test {
 
         assert_eq!(lines.collect::<Vec<_>>(), expected.collect::<Vec<_>>());
     }
+
+    #[test]
+    fn lines_with_limit() {
+        let body = [
+            "abc\r\n",
+            "defghijk",
+            "lmn\r",
+            "\nop\r\nqrstuvwxyz\r\n",
+            "0123",
+            "45\r\n",
+            "6789abcdef",
+        ];
+
+        let lines = Lines::with_limit(
+            stream::iter(&body).map(|&c| Ok(Bytes::from_static(c.as_bytes()))),
+            6,
+        );
+        let lines = block_on_stream(lines)
+            .map(|s: Result<_, Error>| match s {
+                Ok(line) => Ok(String::from_utf8(line.to_vec()).unwrap()),
+                Err(Error::LineTooLong(len)) => Err(len),
+                Err(e) => panic!("{}", e),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            [
+                Ok("abc".to_owned()),
+                Err(11),
+                Ok("op".to_owned()),
+                Err(10),
+                Ok("012345".to_owned()),
+                Err(10),
+            ]
+        );
+    }
 }
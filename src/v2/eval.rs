@@ -0,0 +1,404 @@
+//! Local evaluation of rules against Tweet objects of the v1.1 API.
+
+use json::Value;
+
+use super::syntax::{BoundingBox, DistanceUnit, Expr, Operator, PointRadius};
+
+/// The parts of a Tweet that rules are matched against.
+struct Tweet<'a> {
+    tweet: &'a Value,
+    /// The Tweet whose text and entities are matched, i.e. the original Tweet for a Retweet.
+    content: &'a Value,
+    tokens: Vec<String>,
+}
+
+impl Expr {
+    /// Returns whether a Tweet object of the v1.1 API (e.g. a message yielded by
+    /// a `TwitterStream` connected to `statuses/sample`) matches the rule.
+    ///
+    /// This approximates how the v2 filtered stream matches rules, so that rules can be
+    /// tried on v1.1 streams or recorded archives:
+    ///
+    /// - Keywords and phrases are matched case-insensitively against the words of the text
+    ///   of the Tweet (the full text for extended Tweets and the original text for Retweets),
+    ///   ignoring ASCII punctuation.
+    /// - `context:`, `entity:` and `conversation_id:` never match because v1.1 Tweet objects
+    ///   lack the corresponding fields, and neither does `is:nullcast`.
+    /// - `sample:` keeps Tweets deterministically by their ID.
+    ///
+    /// Messages other than Tweets (e.g. deletion notices) do not match any rule.
+    pub fn matches(&self, tweet: &Value) -> bool {
+        if tweet.get("id_str").is_none() {
+            return false;
+        }
+
+        let content = tweet
+            .get("retweeted_status")
+            .filter(|t| t.is_object())
+            .unwrap_or(tweet);
+        let text = content
+            .pointer("/extended_tweet/full_text")
+            .or_else(|| content.get("full_text"))
+            .or_else(|| content.get("text"))
+            .and_then(Value::as_str)
+            .unwrap_or("");
+
+        let tweet = Tweet {
+            tweet,
+            content,
+            tokens: tokenize(text),
+        };
+        tweet.eval(self)
+    }
+}
+
+impl<'a> Tweet<'a> {
+    fn eval(&self, expr: &Expr) -> bool {
+        match *expr {
+            Expr::And(ref items) => items.iter().all(|e| self.eval(e)),
+            Expr::Or(ref items) => items.iter().any(|e| self.eval(e)),
+            Expr::Not(ref e) => !self.eval(e),
+            Expr::Keyword(ref s) | Expr::Phrase(ref s) => contains_tokens(&self.tokens, s),
+            Expr::Hashtag(ref s) => self.entities("hashtags", "text").any(|t| eq(t, s)),
+            Expr::Mention(ref s) => self
+                .entities("user_mentions", "screen_name")
+                .any(|t| eq(t, s)),
+            Expr::Cashtag(ref s) => self.entities("symbols", "text").any(|t| eq(t, s)),
+            Expr::Operator(op, ref value) => self.operator(op, value),
+            Expr::PointRadius(ref p) => self.within(|lon, lat| in_radius(p, lon, lat)),
+            Expr::BoundingBox(ref b) => self.within(|lon, lat| in_box(b, lon, lat)),
+        }
+    }
+
+    fn operator(&self, op: Operator, value: &str) -> bool {
+        let tweet = self.tweet;
+        let user_is = |user: Option<&Value>| {
+            let user = match user {
+                Some(user) => user,
+                None => return false,
+            };
+            matches!(str_at(user, "/screen_name"), Some(name) if eq(name, value))
+                || str_at(user, "/id_str") == Some(value)
+        };
+
+        match op {
+            Operator::From => user_is(tweet.get("user")),
+            Operator::To => {
+                matches!(str_at(tweet, "/in_reply_to_screen_name"), Some(name) if eq(name, value))
+                    || str_at(tweet, "/in_reply_to_user_id_str") == Some(value)
+            }
+            Operator::RetweetsOf => user_is(tweet.pointer("/retweeted_status/user")),
+            Operator::Url => {
+                let value = value.to_lowercase();
+                self.urls().any(|url| url.to_lowercase().contains(&value))
+            }
+            Operator::Context | Operator::Entity | Operator::ConversationId => false,
+            Operator::Bio => user_field_contains(tweet, "/user/description", value),
+            Operator::BioName => user_field_contains(tweet, "/user/name", value),
+            Operator::BioLocation => user_field_contains(tweet, "/user/location", value),
+            Operator::Place => {
+                str_at(tweet, "/place/id") == Some(value)
+                    || user_field_contains(tweet, "/place/full_name", value)
+            }
+            Operator::PlaceCountry => {
+                matches!(str_at(tweet, "/place/country_code"), Some(c) if eq(c, value))
+            }
+            Operator::Lang => str_at(tweet, "/lang") == Some(value),
+            Operator::Sample => {
+                let id = str_at(tweet, "/id_str").and_then(|id| id.parse::<u64>().ok());
+                let rate = value.parse::<u64>().unwrap_or(0);
+                matches!(id, Some(id) if id % 100 < rate)
+            }
+            Operator::Is => match value {
+                "retweet" => matches!(tweet.get("retweeted_status"), Some(t) if t.is_object()),
+                "reply" => {
+                    matches!(tweet.get("in_reply_to_status_id_str"), Some(id) if !id.is_null())
+                }
+                "quote" => tweet.get("is_quote_status").and_then(Value::as_bool) == Some(true),
+                "verified" => {
+                    tweet.pointer("/user/verified").and_then(Value::as_bool) == Some(true)
+                }
+                _ => false,
+            },
+            Operator::Has => match value {
+                "hashtags" => self.entities("hashtags", "text").next().is_some(),
+                "cashtags" => self.entities("symbols", "text").next().is_some(),
+                "mentions" => self
+                    .entities("user_mentions", "screen_name")
+                    .next()
+                    .is_some(),
+                "links" => self.urls().next().is_some() || self.media().next().is_some(),
+                "media" => self.media().next().is_some(),
+                "images" => self.media().any(|m| str_at(m, "/type") == Some("photo")),
+                "videos" => self
+                    .media()
+                    .any(|m| matches!(str_at(m, "/type"), Some("video") | Some("animated_gif"))),
+                "geo" => ["coordinates", "place"]
+                    .iter()
+                    .any(|k| matches!(tweet.get(k), Some(v) if !v.is_null())),
+                _ => false,
+            },
+        }
+    }
+
+    /// Returns the field `key` of the entities of type `kind`.
+    fn entities(&self, kind: &'a str, key: &'a str) -> impl Iterator<Item = &'a str> {
+        let entities = self
+            .content
+            .pointer("/extended_tweet/entities")
+            .or_else(|| self.content.get("entities"));
+        array_at(entities, kind).filter_map(move |e| e.get(key).and_then(Value::as_str))
+    }
+
+    fn urls(&self) -> impl Iterator<Item = &'a str> {
+        self.entities("urls", "expanded_url")
+            .chain(self.entities("urls", "url"))
+    }
+
+    fn media(&self) -> impl Iterator<Item = &'a Value> {
+        let entities = self
+            .content
+            .pointer("/extended_tweet/extended_entities")
+            .or_else(|| self.content.get("extended_entities"))
+            .or_else(|| self.content.get("entities"));
+        array_at(entities, "media")
+    }
+
+    /// Whether the exact location of the Tweet or all the corners of its Place are in the area.
+    fn within<F: Fn(f64, f64) -> bool>(&self, f: F) -> bool {
+        let tweet = self.tweet;
+        if let Some(point) = tweet.pointer("/coordinates/coordinates") {
+            if let Some((lon, lat)) = lon_lat(point) {
+                return f(lon, lat);
+            }
+        }
+
+        let corners = tweet
+            .pointer("/place/bounding_box/coordinates/0")
+            .and_then(Value::as_array);
+        match corners {
+            Some(corners) if !corners.is_empty() => corners
+                .iter()
+                .all(|c| matches!(lon_lat(c), Some((lon, lat)) if f(lon, lat))),
+            _ => false,
+        }
+    }
+}
+
+fn array_at<'a>(v: Option<&'a Value>, key: &str) -> impl Iterator<Item = &'a Value> {
+    v.and_then(|v| v.get(key))
+        .and_then(Value::as_array)
+        .map_or(&[][..], |a| &a[..])
+        .iter()
+}
+
+fn str_at<'a>(v: &'a Value, pointer: &str) -> Option<&'a str> {
+    v.pointer(pointer).and_then(Value::as_str)
+}
+
+fn lon_lat(point: &Value) -> Option<(f64, f64)> {
+    Some((point.get(0)?.as_f64()?, point.get(1)?.as_f64()?))
+}
+
+fn user_field_contains(tweet: &Value, pointer: &str, value: &str) -> bool {
+    matches!(str_at(tweet, pointer), Some(s) if contains_tokens(&tokenize(s), value))
+}
+
+fn eq(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+/// Whether the words of `s` appear in `tokens` in a row.
+fn contains_tokens(tokens: &[String], s: &str) -> bool {
+    let needle = tokenize(s);
+    !needle.is_empty() && tokens.windows(needle.len()).any(|w| w == &needle[..])
+}
+
+/// Split `s` into lowercase words.
+///
+/// ASCII punctuation and whitespace separate words and are dropped. Other characters that
+/// are not alphanumeric (e.g. emoji) form words of their own.
+fn tokenize(s: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    for c in s.chars() {
+        if c.is_alphanumeric() || c == '_' {
+            word.extend(c.to_lowercase());
+            continue;
+        }
+        if !word.is_empty() {
+            tokens.push(std::mem::take(&mut word));
+        }
+        if !c.is_whitespace() && !c.is_ascii_punctuation() {
+            tokens.push(c.to_string());
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+fn in_radius(p: &PointRadius, lon: f64, lat: f64) -> bool {
+    let earth_radius = match p.unit {
+        DistanceUnit::Miles => 3958.8,
+        DistanceUnit::Kilometers => 6371.0,
+    };
+    // Haversine formula.
+    let (lat1, lat2) = (p.latitude.to_radians(), lat.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (lon - p.longitude).to_radians();
+    let a = (dlat / 2.).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.).sin().powi(2);
+    let distance = 2. * earth_radius * a.sqrt().asin();
+    distance <= p.radius
+}
+
+fn in_box(b: &BoundingBox, lon: f64, lat: f64) -> bool {
+    b.west <= lon && lon <= b.east && b.south <= lat && lat <= b.north
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use json::json;
+
+    fn tweet() -> Value {
+        json!({
+            "id_str": "1050118621198921728",
+            "text": "To make room for more expression, we will now count all emojis as equal\u{2026}",
+            "truncated": true,
+            "lang": "en",
+            "user": {
+                "id_str": "6253282",
+                "screen_name": "TwitterAPI",
+                "name": "Twitter API",
+                "description": "The Real Twitter API. Tweets about API changes.",
+                "location": "San Francisco, CA",
+                "verified": true
+            },
+            "in_reply_to_status_id_str": null,
+            "is_quote_status": false,
+            "coordinates": null,
+            "place": {
+                "id": "5a110d312052166f",
+                "full_name": "San Francisco, CA",
+                "country_code": "US",
+                "bounding_box": {
+                    "type": "Polygon",
+                    "coordinates": [[
+                        [-122.514926, 37.708075],
+                        [-122.357031, 37.708075],
+                        [-122.357031, 37.833238],
+                        [-122.514926, 37.833238]
+                    ]]
+                }
+            },
+            "extended_tweet": {
+                "full_text": "To make room for more expression, we will now count all emojis as equal \u{2014} \
+                              including those with gender and skin tone modifiers \u{1F44D}\u{1F3FB}. \
+                              #Emoji @TwitterDev https://t.co/MkGjXf9aXm",
+                "entities": {
+                    "hashtags": [{"text": "Emoji"}],
+                    "user_mentions": [{"screen_name": "TwitterDev"}],
+                    "symbols": [],
+                    "urls": [{
+                        "url": "https://t.co/MkGjXf9aXm",
+                        "expanded_url": "https://twittercommunity.com/t/new-update-to-the-twitter-text-library-emoji-character-count/114607"
+                    }]
+                },
+                "extended_entities": {
+                    "media": [{"type": "photo"}]
+                }
+            },
+            "entities": {
+                "hashtags": [],
+                "user_mentions": [],
+                "symbols": [],
+                "urls": []
+            }
+        })
+    }
+
+    fn matches(rule: &str, tweet: &Value) -> bool {
+        rule.parse::<Expr>().unwrap().matches(tweet)
+    }
+
+    #[test]
+    fn text() {
+        let tweet = tweet();
+        assert!(matches("emojis", &tweet));
+        assert!(matches("EMOJIS tone", &tweet));
+        assert!(matches("\"skin tone modifiers\"", &tweet));
+        assert!(matches("\u{1F44D}", &tweet));
+        assert!(!matches("\"tone skin\"", &tweet));
+        assert!(!matches("cats", &tweet));
+        assert!(matches("cat OR (emojis -dog)", &tweet));
+        assert!(!matches("emojis -expression", &tweet));
+    }
+
+    #[test]
+    fn entities() {
+        let tweet = tweet();
+        assert!(matches("#emoji @twitterdev", &tweet));
+        assert!(!matches("$TWTR", &tweet));
+        assert!(matches("url:twittercommunity.com", &tweet));
+        assert!(matches("count has:links has:images has:mentions", &tweet));
+        assert!(!matches("count has:videos", &tweet));
+        assert!(!matches("count has:cashtags", &tweet));
+    }
+
+    #[test]
+    fn operators() {
+        let tweet = tweet();
+        assert!(matches("from:twitterapi", &tweet));
+        assert!(matches("from:6253282", &tweet));
+        assert!(!matches("to:twitterapi", &tweet));
+        assert!(matches(
+            "count lang:en is:verified -is:retweet -is:reply -is:quote",
+            &tweet
+        ));
+        assert!(matches("bio:\"api changes\" bio_name:twitter", &tweet));
+        assert!(matches("bio_location:\"san francisco\"", &tweet));
+        assert!(matches(
+            "place:\"san francisco\" place_country:us has:geo",
+            &tweet
+        ));
+        assert!(!matches("context:10.799022225751871488", &tweet));
+        // 1050118621198921728 % 100 == 28
+        assert!(matches("count sample:29", &tweet));
+        assert!(!matches("count sample:28", &tweet));
+
+        let retweet = json!({
+            "id_str": "2",
+            "text": "RT @TwitterAPI: To make room for more expression\u{2026}",
+            "user": {"id_str": "1", "screen_name": "jack"},
+            "retweeted_status": tweet,
+        });
+        assert!(matches(
+            "from:jack retweets_of:TwitterAPI is:retweet",
+            &retweet
+        ));
+        assert!(matches("#Emoji gender", &retweet));
+        assert!(!matches("from:TwitterAPI", &retweet));
+
+        assert!(!matches(
+            "emojis",
+            &json!({"delete": {"status": {"id_str": "1"}}})
+        ));
+    }
+
+    #[test]
+    fn geo() {
+        let mut tweet = tweet();
+        // The Place is fully inside the area.
+        assert!(matches("point_radius:[-122.436 37.77 10mi]", &tweet));
+        assert!(matches("bounding_box:[-122.6 37.7 -122.3 37.9]", &tweet));
+        // The Place is partially outside the area.
+        assert!(!matches("point_radius:[-122.436 37.77 5km]", &tweet));
+
+        tweet["coordinates"] = json!({"type": "Point", "coordinates": [-122.42, 37.78]});
+        assert!(matches("point_radius:[-122.436 37.77 5km]", &tweet));
+        assert!(!matches("bounding_box:[-122.41 37.7 -122.3 37.9]", &tweet));
+    }
+}
@@ -0,0 +1,220 @@
+use std::error;
+use std::fmt::{self, Display, Formatter};
+
+use super::Params;
+
+string_enums! {
+    /// A value of the `tweet.fields` parameter.
+    ///
+    /// See the [Twitter Developer Documentation][1] for more information.
+    ///
+    /// [1]: https://developer.twitter.com/en/docs/twitter-api/data-dictionary/object-model/tweet
+    #[derive(Clone, Debug)]
+    pub enum TweetField {
+        Attachments("attachments"),
+        AuthorId("author_id"),
+        ContextAnnotations("context_annotations"),
+        ConversationId("conversation_id"),
+        CreatedAt("created_at"),
+        Entities("entities"),
+        Geo("geo"),
+        Id("id"),
+        InReplyToUserId("in_reply_to_user_id"),
+        Lang("lang"),
+        NonPublicMetrics("non_public_metrics"),
+        OrganicMetrics("organic_metrics"),
+        PossiblySensitive("possibly_sensitive"),
+        PromotedMetrics("promoted_metrics"),
+        PublicMetrics("public_metrics"),
+        ReferencedTweets("referenced_tweets"),
+        ReplySettings("reply_settings"),
+        Source("source"),
+        Text("text"),
+        Withheld("withheld");
+        Custom(_),
+    }
+
+    /// A value of the `user.fields` parameter.
+    ///
+    /// See the [Twitter Developer Documentation][1] for more information.
+    ///
+    /// [1]: https://developer.twitter.com/en/docs/twitter-api/data-dictionary/object-model/user
+    #[derive(Clone, Debug)]
+    pub enum UserField {
+        CreatedAt("created_at"),
+        Description("description"),
+        Entities("entities"),
+        Id("id"),
+        Location("location"),
+        Name("name"),
+        PinnedTweetId("pinned_tweet_id"),
+        ProfileImageUrl("profile_image_url"),
+        Protected("protected"),
+        PublicMetrics("public_metrics"),
+        Url("url"),
+        Username("username"),
+        Verified("verified"),
+        Withheld("withheld");
+        Custom(_),
+    }
+
+    /// A value of the `media.fields` parameter.
+    ///
+    /// See the [Twitter Developer Documentation][1] for more information.
+    ///
+    /// [1]: https://developer.twitter.com/en/docs/twitter-api/data-dictionary/object-model/media
+    #[derive(Clone, Debug)]
+    pub enum MediaField {
+        AltText("alt_text"),
+        DurationMs("duration_ms"),
+        Height("height"),
+        MediaKey("media_key"),
+        NonPublicMetrics("non_public_metrics"),
+        OrganicMetrics("organic_metrics"),
+        PreviewImageUrl("preview_image_url"),
+        PromotedMetrics("promoted_metrics"),
+        PublicMetrics("public_metrics"),
+        Type("type"),
+        Url("url"),
+        Width("width");
+        Custom(_),
+    }
+
+    /// A value of the `place.fields` parameter.
+    ///
+    /// See the [Twitter Developer Documentation][1] for more information.
+    ///
+    /// [1]: https://developer.twitter.com/en/docs/twitter-api/data-dictionary/object-model/place
+    #[derive(Clone, Debug)]
+    pub enum PlaceField {
+        ContainedWithin("contained_within"),
+        Country("country"),
+        CountryCode("country_code"),
+        FullName("full_name"),
+        Geo("geo"),
+        Id("id"),
+        Name("name"),
+        PlaceType("place_type");
+        Custom(_),
+    }
+
+    /// A value of the `poll.fields` parameter.
+    ///
+    /// See the [Twitter Developer Documentation][1] for more information.
+    ///
+    /// [1]: https://developer.twitter.com/en/docs/twitter-api/data-dictionary/object-model/poll
+    #[derive(Clone, Debug)]
+    pub enum PollField {
+        DurationMinutes("duration_minutes"),
+        EndDatetime("end_datetime"),
+        Id("id"),
+        Options("options"),
+        VotingStatus("voting_status");
+        Custom(_),
+    }
+
+    /// A value of the `expansions` parameter.
+    ///
+    /// See the [Twitter Developer Documentation][1] for more information.
+    ///
+    /// [1]: https://developer.twitter.com/en/docs/twitter-api/expansions
+    #[derive(Clone, Debug)]
+    pub enum Expansion {
+        AttachmentsPollIds("attachments.poll_ids"),
+        AttachmentsMediaKeys("attachments.media_keys"),
+        AuthorId("author_id"),
+        EntitiesMentionsUsername("entities.mentions.username"),
+        GeoPlaceId("geo.place_id"),
+        InReplyToUserId("in_reply_to_user_id"),
+        ReferencedTweetsId("referenced_tweets.id"),
+        ReferencedTweetsIdAuthorId("referenced_tweets.id.author_id");
+        Custom(_),
+    }
+}
+
+/// An inconsistent combination of fields and expansions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldsError {
+    /// The fields parameter (e.g. `user.fields`) has no effect without an expansion
+    /// that includes the objects.
+    MissingExpansion(&'static str),
+    /// The field (e.g. `tweet.fields=non_public_metrics`) requires user context
+    /// authentication, which is not available on the streaming endpoints.
+    PrivateMetrics(String),
+}
+
+const USER_EXPANSIONS: &[Expansion] = &[
+    Expansion::AuthorId,
+    Expansion::EntitiesMentionsUsername,
+    Expansion::InReplyToUserId,
+    Expansion::ReferencedTweetsIdAuthorId,
+];
+
+const PRIVATE_METRICS: &[&str] = &["non_public_metrics", "organic_metrics", "promoted_metrics"];
+
+impl<'a> Params<'a> {
+    pub fn validate(&self) -> Result<(), FieldsError> {
+        let expansions = self.expansions.unwrap_or(&[]);
+        let requires = |given: bool, name: &'static str, any_of: &[Expansion]| {
+            if given && !expansions.iter().any(|e| any_of.contains(e)) {
+                Err(FieldsError::MissingExpansion(name))
+            } else {
+                Ok(())
+            }
+        };
+
+        requires(not_empty(self.user_fields), "user.fields", USER_EXPANSIONS)?;
+        requires(
+            not_empty(self.media_fields),
+            "media.fields",
+            &[Expansion::AttachmentsMediaKeys],
+        )?;
+        requires(
+            not_empty(self.place_fields),
+            "place.fields",
+            &[Expansion::GeoPlaceId],
+        )?;
+        requires(
+            not_empty(self.poll_fields),
+            "poll.fields",
+            &[Expansion::AttachmentsPollIds],
+        )?;
+
+        let private = |name: &str, field: &str| {
+            if PRIVATE_METRICS.contains(&field) {
+                Err(FieldsError::PrivateMetrics(format!("{}={}", name, field)))
+            } else {
+                Ok(())
+            }
+        };
+        for field in self.tweet_fields.unwrap_or(&[]) {
+            private("tweet.fields", field.as_ref())?;
+        }
+        for field in self.media_fields.unwrap_or(&[]) {
+            private("media.fields", field.as_ref())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn not_empty<T>(fields: Option<&[T]>) -> bool {
+    matches!(fields, Some(fields) if !fields.is_empty())
+}
+
+impl error::Error for FieldsError {}
+
+impl Display for FieldsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            FieldsError::MissingExpansion(name) => {
+                write!(f, "`{}` requires a corresponding expansion", name)
+            }
+            FieldsError::PrivateMetrics(ref field) => write!(
+                f,
+                "`{}` is not available without user context authentication",
+                field
+            ),
+        }
+    }
+}
@@ -0,0 +1,505 @@
+//! Twitter API v2 streaming endpoints.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use futures::prelude::*;
+//! use twitter_stream::v2::{NewRule, Rules, StreamMessage};
+//! use twitter_stream::BearerToken;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let conn = hyper_tls::HttpsConnector::new();
+//! # let client = hyper_pkg::Client::builder().build::<_, hyper_pkg::Body>(conn);
+//! let token = BearerToken::new("bearer_token");
+//!
+//! Rules::new(token.as_ref())
+//!     .add(&[NewRule::new("@Twitter", "mentions")], client)
+//!     .await
+//!     .unwrap();
+//!
+//! twitter_stream::v2::Builder::filter(token)
+//!     .listen()
+//!     .try_flatten_stream()
+//!     .try_for_each(|line| {
+//!         let msg: StreamMessage = json::from_str(&line).unwrap();
+//!         if let Some(tweet) = msg.expand() {
+//!             let author = tweet.author.map_or("?", |user| &user.username);
+//!             println!("@{}: {}", author, tweet.tweet.text);
+//!         }
+//!         future::ok(())
+//!     })
+//!     .await
+//!     .unwrap();
+//! # }
+//! ```
+
+pub mod syntax;
+
+mod eval;
+mod fields;
+mod model;
+mod problem;
+mod rules;
+mod sync;
+
+pub use self::fields::{
+    Expansion, FieldsError, MediaField, PlaceField, PollField, TweetField, UserField,
+};
+pub use self::model::{
+    Attachments, ExpandedReference, ExpandedTweet, Geo, Includes, MatchingRule, Media, Place, Poll,
+    PollOption, ReferenceType, ReferencedTweet, StreamMessage, Tweet, User,
+};
+pub use self::problem::{Problem, ProblemMessage, ProblemType};
+pub use self::rules::{
+    AddRulesResponse, AddSummary, DeleteRulesResponse, DeleteSummary, NewRule, Rule, RuleError,
+    Rules,
+};
+pub use self::sync::{RuleDiff, SyncReport};
+
+use std::future::Future;
+
+use bytes::Bytes;
+use http::header::{HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response};
+use http_body::Body;
+use serde::de::DeserializeOwned;
+use tower_service::Service;
+
+use crate::auth::{Authorizer, BearerToken, SignatureBase};
+use crate::error::Error;
+use crate::types::{RequestMethod, Uri};
+use crate::util::*;
+use crate::{ClockSkewRetry, FutureTwitterStream};
+
+/// A builder for a `TwitterStream` connected to a v2 streaming endpoint.
+///
+/// The `TwitterStream` yields the JSON messages from the endpoint in the same way as for
+/// the v1.1 endpoints.
+#[derive(Clone, Debug)]
+pub struct Builder<'a, T = BearerToken> {
+    inner: crate::Builder<'a, T, Params<'a>>,
+}
+
+#[derive(Clone, Debug, Default, oauth::Authorize)]
+struct Params<'a> {
+    #[oauth1(encoded)]
+    backfill_minutes: Option<u32>,
+    #[oauth1(fmt = "fmt_list")]
+    expansions: Option<&'a [Expansion]>,
+    #[oauth1(rename = "media.fields", fmt = "fmt_list")]
+    media_fields: Option<&'a [MediaField]>,
+    #[oauth1(rename = "place.fields", fmt = "fmt_list")]
+    place_fields: Option<&'a [PlaceField]>,
+    #[oauth1(rename = "poll.fields", fmt = "fmt_list")]
+    poll_fields: Option<&'a [PollField]>,
+    #[oauth1(rename = "tweet.fields", fmt = "fmt_list")]
+    tweet_fields: Option<&'a [TweetField]>,
+    #[oauth1(rename = "user.fields", fmt = "fmt_list")]
+    user_fields: Option<&'a [UserField]>,
+}
+
+impl<'a, T: Authorizer> Builder<'a, T> {
+    /// Create a builder for `GET /2/tweets/search/stream` endpoint.
+    ///
+    /// The Tweets to be delivered are determined by the rules managed with `Rules`.
+    ///
+    /// See the [Twitter Developer Documentation][1] for more information.
+    ///
+    /// [1]: https://developer.twitter.com/en/docs/twitter-api/tweets/filtered-stream/api-reference/get-tweets-search-stream
+    pub fn filter(token: T) -> Self {
+        const URI: &str = "https://api.twitter.com/2/tweets/search/stream";
+        Builder {
+            inner: crate::Builder::custom(RequestMethod::GET, Uri::from_static(URI), token)
+                .with_params(Params::default()),
+        }
+    }
+
+    /// Create a builder for `GET /2/tweets/sample/stream` endpoint.
+    ///
+    /// The endpoint delivers a random sample of about 1% of all public Tweets.
+    ///
+    /// See the [Twitter Developer Documentation][1] for more information.
+    ///
+    /// [1]: https://developer.twitter.com/en/docs/twitter-api/tweets/volume-streams/api-reference/get-tweets-sample-stream
+    pub fn sample(token: T) -> Self {
+        const URI: &str = "https://api.twitter.com/2/tweets/sample/stream";
+        Builder {
+            inner: crate::Builder::custom(RequestMethod::GET, Uri::from_static(URI), token)
+                .with_params(Params::default()),
+        }
+    }
+
+    /// Start listening on the Streaming API endpoint, returning a `Future` which resolves
+    /// to a `Stream` yielding JSON messages from the API.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the underlying HTTPS connector failed to initialize.
+    #[cfg(feature = "hyper")]
+    pub fn listen(&self) -> crate::hyper::FutureTwitterStream {
+        let conn = hyper_tls::HttpsConnector::new();
+        self.listen_with_client(hyper_pkg::Client::builder().build::<_, hyper_pkg::Body>(conn))
+    }
+
+    /// Same as `listen` except that it uses `client` to make HTTP request to the endpoint.
+    ///
+    /// If the fields and expansions are inconsistent (see `validate`), the future resolves to
+    /// `Error::Custom` with the `FieldsError` without sending a request.
+    ///
+    /// # Panics
+    ///
+    /// This will call `<S as Service>::call` without checking for `<S as Service>::poll_ready`
+    /// and may cause a panic if `client` is not ready to send an HTTP request yet.
+    pub fn listen_with_client<S, ReqB, ResB>(
+        &self,
+        client: S,
+    ) -> FutureTwitterStream<S::Future, ClockSkewRetry<S, ReqB>>
+    where
+        S: Service<Request<ReqB>, Response = Response<ResB>>,
+        ReqB: Default + From<Vec<u8>>,
+        ResB: Body<Data = Bytes>,
+    {
+        match self.validate() {
+            Ok(()) => self.inner.listen_with_client(client),
+            Err(e) => FutureTwitterStream::error(e),
+        }
+    }
+
+    /// Returns the signed request that `listen` would send, without sending it.
+    /// See `twitter_stream::Builder::build_request`.
+    ///
+    /// Returns an error if the fields and expansions are inconsistent (see `validate`).
+    pub fn build_request(&self) -> Result<Request<Vec<u8>>, FieldsError> {
+        self.validate()?;
+        Ok(self.inner.build_request())
+    }
+
+    /// Returns the OAuth 1.0a signature base string and the normalized parameters of
+    /// the request that `listen` would send. See `twitter_stream::Builder::signature_base`.
+    pub fn signature_base(&self) -> Option<SignatureBase> {
+        self.inner.signature_base()
+    }
+}
+
+impl<'a, T> Builder<'a, T> {
+    /// Check that the fields and expansions make sense together.
+    ///
+    /// A `*.fields` parameter other than `tweet.fields` requires an expansion that includes
+    /// the corresponding objects, and the private metrics fields are not available on
+    /// the streaming endpoints.
+    pub fn validate(&self) -> Result<(), FieldsError> {
+        self.inner.params.validate()
+    }
+
+    /// Reset the API endpoint URI to be connected.
+    pub fn endpoint(&mut self, endpoint: Uri) -> &mut Self {
+        self.inner.endpoint(endpoint);
+        self
+    }
+
+    /// Reset the token to be used to log into Twitter.
+    pub fn token(&mut self, token: T) -> &mut Self {
+        self.inner.token(token);
+        self
+    }
+
+    /// Set the value of the `Accept-Encoding` header. See `twitter_stream::Builder::accept_encoding`.
+    pub fn accept_encoding(
+        &mut self,
+        accept_encoding: impl Into<Option<HeaderValue>>,
+    ) -> &mut Self {
+        self.inner.accept_encoding(accept_encoding);
+        self
+    }
+
+    /// Add a header to be sent with the request. See `twitter_stream::Builder::header`.
+    pub fn header(&mut self, name: HeaderName, value: HeaderValue) -> &mut Self {
+        self.inner.header(name, value);
+        self
+    }
+
+    /// Add a request parameter that has no dedicated method.
+    /// See `twitter_stream::Builder::param`.
+    pub fn param(&mut self, key: &'a str, value: &'a str) -> &mut Self {
+        self.inner.param(key, value);
+        self
+    }
+
+    /// Set the maximum length of a line. See `twitter_stream::Builder::max_line_len`.
+    pub fn max_line_len(&mut self, max_line_len: impl Into<Option<usize>>) -> &mut Self {
+        self.inner.max_line_len(max_line_len);
+        self
+    }
+
+    /// Set whether to detect gzip-compressed responses by their content.
+    /// See `twitter_stream::Builder::sniff_gzip`.
+    pub fn sniff_gzip(&mut self, sniff_gzip: bool) -> &mut Self {
+        self.inner.sniff_gzip(sniff_gzip);
+        self
+    }
+
+    /// Set the number of minutes (up to 5) of Tweets to be delivered from before
+    /// the connection was made, to recover from a disconnection.
+    /// This parameter requires Academic Research access.
+    pub fn backfill_minutes(&mut self, backfill_minutes: impl Into<Option<u32>>) -> &mut Self {
+        self.inner.params.backfill_minutes = backfill_minutes.into();
+        self
+    }
+
+    /// Set the objects referenced by the Tweets to be included in the `includes` object.
+    pub fn expansions(&mut self, expansions: impl Into<Option<&'a [Expansion]>>) -> &mut Self {
+        self.inner.params.expansions = expansions.into();
+        self
+    }
+
+    /// Set the fields of the Tweet objects to be returned.
+    pub fn tweet_fields(&mut self, fields: impl Into<Option<&'a [TweetField]>>) -> &mut Self {
+        self.inner.params.tweet_fields = fields.into();
+        self
+    }
+
+    /// Set the fields of the expanded user objects to be returned.
+    pub fn user_fields(&mut self, fields: impl Into<Option<&'a [UserField]>>) -> &mut Self {
+        self.inner.params.user_fields = fields.into();
+        self
+    }
+
+    /// Set the fields of the expanded media objects to be returned.
+    pub fn media_fields(&mut self, fields: impl Into<Option<&'a [MediaField]>>) -> &mut Self {
+        self.inner.params.media_fields = fields.into();
+        self
+    }
+
+    /// Set the fields of the expanded place objects to be returned.
+    pub fn place_fields(&mut self, fields: impl Into<Option<&'a [PlaceField]>>) -> &mut Self {
+        self.inner.params.place_fields = fields.into();
+        self
+    }
+
+    /// Set the fields of the expanded poll objects to be returned.
+    pub fn poll_fields(&mut self, fields: impl Into<Option<&'a [PollField]>>) -> &mut Self {
+        self.inner.params.poll_fields = fields.into();
+        self
+    }
+}
+
+/// Send a request to a REST endpoint and parse the JSON response.
+///
+/// `query` is the encoded query string and `body` is the JSON request body, if any.
+/// An error response is converted to `Error::Api` if it contains a problem object.
+fn send<A, S, ReqB, ResB, R>(
+    token: &A,
+    method: RequestMethod,
+    endpoint: &Uri,
+    query: &str,
+    body: Option<Vec<u8>>,
+    mut client: S,
+) -> impl Future<Output = Result<R, Error<S::Error>>>
+where
+    A: Authorizer,
+    S: Service<Request<ReqB>, Response = Response<ResB>>,
+    ReqB: From<Vec<u8>>,
+    ResB: Body<Error = S::Error>,
+    R: DeserializeOwned,
+{
+    let mut req = Request::builder().method(method.clone());
+    let headers = token.authorize(&method, endpoint, query);
+    req.headers_mut().unwrap().extend(headers);
+
+    req = if query.is_empty() {
+        req.uri(endpoint.clone())
+    } else {
+        req.uri(format!("{}?{}", endpoint, query))
+    };
+
+    let req = if let Some(body) = body {
+        req.header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+            .header(CONTENT_LENGTH, body.len())
+            .body(body.into())
+            .unwrap()
+    } else {
+        req.body(Vec::new().into()).unwrap()
+    };
+    let response = client.call(req);
+
+    async move {
+        let res = response.await.map_err(Error::Service)?;
+        let status = res.status();
+        let body = read_body(res.into_body()).await?;
+
+        if !status.is_success() {
+            return Err(match json::from_slice::<Problem>(&body) {
+                Ok(mut problem) => {
+                    problem.status = status;
+                    Error::Api(problem)
+                }
+                Err(_) => Error::Http(status),
+            });
+        }
+
+        json::from_slice(&body).map_err(Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures_executor::block_on;
+
+    use crate::types::StatusCode;
+    use crate::util::{respond, ServiceFn};
+
+    #[test]
+    fn filter_stream() {
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            assert_eq!(req.method(), RequestMethod::GET);
+            assert_eq!(
+                req.uri(),
+                "https://api.twitter.com/2/tweets/search/stream?backfill_minutes=2",
+            );
+            assert_eq!(req.headers()["authorization"], "Bearer AAAA");
+            respond(
+                StatusCode::OK,
+                "{\"data\":{\"id\":\"1\",\"text\":\"a\"}}\r\n\r\n",
+            )
+        });
+
+        let stream = Builder::filter(BearerToken::new("AAAA"))
+            .backfill_minutes(2)
+            .listen_with_client(client);
+        let lines = futures_executor::block_on_stream(block_on(stream).unwrap())
+            .map(|line| line.unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(lines, ["{\"data\":{\"id\":\"1\",\"text\":\"a\"}}"]);
+    }
+
+    #[test]
+    fn sample_stream() {
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            assert_eq!(
+                req.uri(),
+                "https://api.twitter.com/2/tweets/sample/stream\
+                 ?expansions=author_id%2Cgeo.place_id\
+                 &place.fields=full_name\
+                 &tweet.fields=created_at%2Clang\
+                 &user.fields=username",
+            );
+            respond(StatusCode::OK, "")
+        });
+
+        let stream = Builder::sample(BearerToken::new("AAAA"))
+            .tweet_fields(&[TweetField::CreatedAt, TweetField::Lang][..])
+            .expansions(&[Expansion::AuthorId, Expansion::GeoPlaceId][..])
+            .user_fields(&[UserField::Username][..])
+            .place_fields(&[PlaceField::FullName][..])
+            .listen_with_client(client);
+        block_on(stream).unwrap();
+    }
+
+    #[test]
+    fn fields_validation() {
+        let token = BearerToken::new("AAAA");
+        let mut builder = Builder::sample(&token);
+        assert_eq!(builder.validate(), Ok(()));
+
+        builder.user_fields(&[UserField::Username][..]);
+        assert_eq!(
+            builder.validate(),
+            Err(FieldsError::MissingExpansion("user.fields"))
+        );
+        builder.expansions(&[Expansion::ReferencedTweetsIdAuthorId][..]);
+        assert_eq!(builder.validate(), Ok(()));
+
+        builder.media_fields(&[MediaField::Url][..]);
+        assert_eq!(
+            builder.validate(),
+            Err(FieldsError::MissingExpansion("media.fields"))
+        );
+        builder.media_fields(&[][..]);
+        assert_eq!(builder.validate(), Ok(()));
+
+        builder.tweet_fields(&[TweetField::Id, TweetField::NonPublicMetrics][..]);
+        assert_eq!(
+            builder.validate(),
+            Err(FieldsError::PrivateMetrics(
+                "tweet.fields=non_public_metrics".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn invalid_fields() {
+        let client = ServiceFn(
+            |_: Request<Vec<u8>>| -> Response<ChunksBody<std::iter::Once<Bytes>>> {
+                panic!("unexpected request")
+            },
+        );
+        let mut builder = Builder::sample(BearerToken::new("AAAA"));
+        builder.poll_fields(&[PollField::Options][..]);
+
+        assert_eq!(
+            builder.build_request().unwrap_err(),
+            FieldsError::MissingExpansion("poll.fields")
+        );
+        match block_on(builder.listen_with_client(client)) {
+            Err(Error::Custom(e)) => assert_eq!(
+                e.downcast_ref::<FieldsError>(),
+                Some(&FieldsError::MissingExpansion("poll.fields"))
+            ),
+            res => panic!("expected a fields error, got {:?}", res.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn problem() {
+        let client = ServiceFn(|_: Request<Vec<u8>>| {
+            respond(
+                StatusCode::TOO_MANY_REQUESTS,
+                r#"{
+                    "title": "ConnectionException",
+                    "detail": "This stream is currently at the maximum allowed connection limit.",
+                    "connection_issue": "TooManyConnections",
+                    "type": "https://api.twitter.com/2/problems/streaming-connection"
+                }"#,
+            )
+        });
+        let token = BearerToken::new("AAAA");
+        let uri = Uri::from_static("https://api.twitter.com/2/tweets/search/stream/rules");
+        let res = block_on(send::<_, _, _, _, json::Value>(
+            &token,
+            RequestMethod::GET,
+            &uri,
+            "",
+            None,
+            client,
+        ));
+        match res {
+            Err(Error::Api(ref problem)) => {
+                assert_eq!(problem.status, StatusCode::TOO_MANY_REQUESTS);
+                assert_eq!(problem.kind, ProblemType::StreamingConnection);
+                assert_eq!(problem.title, "ConnectionException");
+            }
+            ref res => panic!("{:?}", res),
+        }
+        assert_eq!(
+            res.unwrap_err().backoff(),
+            Some(crate::error::Backoff::RateLimited)
+        );
+
+        let client = ServiceFn(|_: Request<Vec<u8>>| respond(StatusCode::BAD_GATEWAY, "<html>"));
+        let res = block_on(send::<_, _, _, _, json::Value>(
+            &token,
+            RequestMethod::GET,
+            &uri,
+            "",
+            None,
+            client,
+        ));
+        match res {
+            Err(Error::Http(StatusCode::BAD_GATEWAY)) => {}
+            res => panic!("{:?}", res),
+        }
+    }
+}
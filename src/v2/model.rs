@@ -0,0 +1,368 @@
+use serde::Deserialize;
+
+/// A message from a v2 streaming endpoint.
+///
+/// The objects referenced by the Tweet are delivered separately in `includes`.
+/// Use `StreamMessage::expand` to resolve them.
+///
+/// See the [Twitter Developer Documentation][1] for more information.
+///
+/// [1]: https://developer.twitter.com/en/docs/twitter-api/expansions
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct StreamMessage {
+    /// The Tweet, which is absent if the message only carries `errors`.
+    #[serde(default)]
+    pub data: Option<Tweet>,
+    #[serde(default)]
+    pub includes: Includes,
+    /// The rules of the filtered stream that the Tweet matched.
+    #[serde(default)]
+    pub matching_rules: Vec<MatchingRule>,
+    /// Partial errors, e.g. for referenced objects that could not be included.
+    #[serde(default)]
+    pub errors: Vec<json::Value>,
+}
+
+/// The objects referenced by the Tweets of a message, requested with `expansions`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Includes {
+    #[serde(default)]
+    pub tweets: Vec<Tweet>,
+    #[serde(default)]
+    pub users: Vec<User>,
+    #[serde(default)]
+    pub media: Vec<Media>,
+    #[serde(default)]
+    pub places: Vec<Place>,
+    #[serde(default)]
+    pub polls: Vec<Poll>,
+}
+
+/// A rule that a Tweet from the filtered stream matched.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub struct MatchingRule {
+    pub id: String,
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+/// A v2 Tweet object.
+///
+/// The fields that are not modeled here (e.g. those requested with `tweet.fields`)
+/// are kept in `extra`.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct Tweet {
+    pub id: String,
+    pub text: String,
+    #[serde(default)]
+    pub author_id: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub conversation_id: Option<String>,
+    #[serde(default)]
+    pub in_reply_to_user_id: Option<String>,
+    #[serde(default)]
+    pub lang: Option<String>,
+    #[serde(default)]
+    pub attachments: Option<Attachments>,
+    #[serde(default)]
+    pub geo: Option<Geo>,
+    #[serde(default)]
+    pub referenced_tweets: Vec<ReferencedTweet>,
+    #[serde(flatten)]
+    pub extra: json::Map<String, json::Value>,
+}
+
+/// The `attachments` of a v2 Tweet.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct Attachments {
+    #[serde(default)]
+    pub media_keys: Vec<String>,
+    #[serde(default)]
+    pub poll_ids: Vec<String>,
+}
+
+/// The `geo` object of a v2 Tweet.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct Geo {
+    #[serde(default)]
+    pub place_id: Option<String>,
+    /// The exact location of the Tweet as a GeoJSON point, if any.
+    #[serde(default)]
+    pub coordinates: Option<json::Value>,
+}
+
+/// A reference from a v2 Tweet to another Tweet.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct ReferencedTweet {
+    #[serde(rename = "type")]
+    pub kind: ReferenceType,
+    pub id: String,
+}
+
+string_enums! {
+    /// The `type` of a `ReferencedTweet`.
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(from = "String")]
+    pub enum ReferenceType {
+        RepliedTo("replied_to"),
+        Quoted("quoted"),
+        Retweeted("retweeted");
+        Custom(_),
+    }
+}
+
+impl From<String> for ReferenceType {
+    fn from(s: String) -> Self {
+        match &*s {
+            "replied_to" => ReferenceType::RepliedTo,
+            "quoted" => ReferenceType::Quoted,
+            "retweeted" => ReferenceType::Retweeted,
+            _ => ReferenceType::Custom(s),
+        }
+    }
+}
+
+/// A v2 user object.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct User {
+    pub id: String,
+    pub name: String,
+    pub username: String,
+    #[serde(flatten)]
+    pub extra: json::Map<String, json::Value>,
+}
+
+/// A v2 media object.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct Media {
+    pub media_key: String,
+    /// The type of the media, e.g. `photo`, `animated_gif` or `video`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(flatten)]
+    pub extra: json::Map<String, json::Value>,
+}
+
+/// A v2 place object.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct Place {
+    pub id: String,
+    pub full_name: String,
+    #[serde(flatten)]
+    pub extra: json::Map<String, json::Value>,
+}
+
+/// A v2 poll object.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct Poll {
+    pub id: String,
+    #[serde(default)]
+    pub options: Vec<PollOption>,
+    #[serde(flatten)]
+    pub extra: json::Map<String, json::Value>,
+}
+
+/// An option of a `Poll`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct PollOption {
+    pub position: u32,
+    pub label: String,
+    #[serde(default)]
+    pub votes: u64,
+}
+
+/// A Tweet with the objects it references resolved from `Includes`.
+///
+/// The referenced objects that are missing from `includes` (because they were not
+/// requested, or are not available) are left out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpandedTweet<'a> {
+    pub tweet: &'a Tweet,
+    /// The user referenced by `author_id`.
+    pub author: Option<&'a User>,
+    /// The media referenced by `attachments.media_keys`.
+    pub media: Vec<&'a Media>,
+    /// The polls referenced by `attachments.poll_ids`.
+    pub polls: Vec<&'a Poll>,
+    /// The place referenced by `geo.place_id`.
+    pub place: Option<&'a Place>,
+    /// The Tweets referenced by `referenced_tweets`.
+    pub referenced_tweets: Vec<ExpandedReference<'a>>,
+    /// The rules that the Tweet matched, if it is the main Tweet of a filtered stream message.
+    pub matching_rules: &'a [MatchingRule],
+}
+
+/// A `ReferencedTweet` with the referenced Tweet resolved from `Includes`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpandedReference<'a> {
+    pub kind: &'a ReferenceType,
+    pub id: &'a str,
+    /// The referenced Tweet, whose own references are not expanded further.
+    pub tweet: Option<ExpandedTweet<'a>>,
+}
+
+impl StreamMessage {
+    /// Resolve the objects referenced by the Tweet of the message.
+    ///
+    /// Returns `None` if the message has no Tweet.
+    pub fn expand(&self) -> Option<ExpandedTweet<'_>> {
+        self.data.as_ref().map(|tweet| {
+            let mut expanded = self.includes.expand(tweet);
+            expanded.matching_rules = &self.matching_rules;
+            expanded
+        })
+    }
+}
+
+impl Includes {
+    /// Resolve the objects referenced by `tweet` from `self`.
+    pub fn expand<'a>(&'a self, tweet: &'a Tweet) -> ExpandedTweet<'a> {
+        let mut expanded = self.expand_shallow(tweet);
+        expanded.referenced_tweets = tweet
+            .referenced_tweets
+            .iter()
+            .map(|r| ExpandedReference {
+                kind: &r.kind,
+                id: &r.id,
+                tweet: self.tweet(&r.id).map(|t| self.expand_shallow(t)),
+            })
+            .collect();
+        expanded
+    }
+
+    /// Returns the included Tweet with the given ID.
+    pub fn tweet(&self, id: &str) -> Option<&Tweet> {
+        self.tweets.iter().find(|t| t.id == id)
+    }
+
+    /// Returns the included user with the given ID.
+    pub fn user(&self, id: &str) -> Option<&User> {
+        self.users.iter().find(|u| u.id == id)
+    }
+
+    /// Returns the included media with the given media key.
+    pub fn media(&self, media_key: &str) -> Option<&Media> {
+        self.media.iter().find(|m| m.media_key == media_key)
+    }
+
+    /// Returns the included place with the given ID.
+    pub fn place(&self, id: &str) -> Option<&Place> {
+        self.places.iter().find(|p| p.id == id)
+    }
+
+    /// Returns the included poll with the given ID.
+    pub fn poll(&self, id: &str) -> Option<&Poll> {
+        self.polls.iter().find(|p| p.id == id)
+    }
+
+    fn expand_shallow<'a>(&'a self, tweet: &'a Tweet) -> ExpandedTweet<'a> {
+        let attachments = tweet.attachments.as_ref();
+        let media_keys = attachments.map_or(&[][..], |a| &a.media_keys);
+        let poll_ids = attachments.map_or(&[][..], |a| &a.poll_ids);
+        ExpandedTweet {
+            tweet,
+            author: tweet.author_id.as_ref().and_then(|id| self.user(id)),
+            media: media_keys.iter().filter_map(|k| self.media(k)).collect(),
+            polls: poll_ids.iter().filter_map(|id| self.poll(id)).collect(),
+            place: tweet
+                .geo
+                .as_ref()
+                .and_then(|geo| geo.place_id.as_ref())
+                .and_then(|id| self.place(id)),
+            referenced_tweets: Vec::new(),
+            matching_rules: &[],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expand() {
+        let msg: StreamMessage = json::from_str(
+            r#"{
+                "data": {
+                    "id": "3",
+                    "text": "look",
+                    "author_id": "10",
+                    "lang": "en",
+                    "attachments": {"media_keys": ["3_1", "3_2"]},
+                    "geo": {"place_id": "p"},
+                    "referenced_tweets": [
+                        {"type": "quoted", "id": "2"},
+                        {"type": "replied_to", "id": "404"}
+                    ],
+                    "public_metrics": {"retweet_count": 0}
+                },
+                "includes": {
+                    "users": [
+                        {"id": "10", "name": "Alice", "username": "alice"},
+                        {"id": "20", "name": "Bob", "username": "bob", "verified": true}
+                    ],
+                    "tweets": [{
+                        "id": "2",
+                        "text": "original",
+                        "author_id": "20",
+                        "referenced_tweets": [{"type": "quoted", "id": "1"}]
+                    }],
+                    "media": [{"media_key": "3_1", "type": "photo"}],
+                    "places": [{"id": "p", "full_name": "Tokyo, Japan"}]
+                },
+                "matching_rules": [{"id": "42", "tag": "cats"}]
+            }"#,
+        )
+        .unwrap();
+
+        let tweet = msg.data.as_ref().unwrap();
+        assert_eq!(tweet.extra["public_metrics"]["retweet_count"], 0);
+        assert_eq!(tweet.referenced_tweets[0].kind, ReferenceType::Quoted);
+
+        let expanded = msg.expand().unwrap();
+        assert_eq!(expanded.tweet.id, "3");
+        assert_eq!(expanded.author.unwrap().username, "alice");
+        assert_eq!(expanded.media.len(), 1);
+        assert_eq!(expanded.media[0].kind, "photo");
+        assert!(expanded.polls.is_empty());
+        assert_eq!(expanded.place.unwrap().full_name, "Tokyo, Japan");
+        assert_eq!(
+            expanded.matching_rules,
+            [MatchingRule {
+                id: "42".into(),
+                tag: Some("cats".into()),
+            }]
+        );
+
+        let quoted = &expanded.referenced_tweets[0];
+        assert_eq!(*quoted.kind, ReferenceType::Quoted);
+        let quoted = quoted.tweet.as_ref().unwrap();
+        assert_eq!(quoted.tweet.text, "original");
+        assert_eq!(quoted.author.unwrap().extra["verified"], true);
+        assert!(quoted.referenced_tweets.is_empty());
+        assert!(quoted.matching_rules.is_empty());
+
+        let reply = &expanded.referenced_tweets[1];
+        assert_eq!(*reply.kind, ReferenceType::RepliedTo);
+        assert_eq!(reply.id, "404");
+        assert!(reply.tweet.is_none());
+    }
+
+    #[test]
+    fn errors_only() {
+        let msg: StreamMessage = json::from_str(
+            r#"{"errors": [{
+                "title": "operational-disconnect",
+                "disconnect_type": "UpstreamOperationalDisconnect",
+                "type": "https://api.twitter.com/2/problems/operational-disconnect"
+            }]}"#,
+        )
+        .unwrap();
+        assert!(msg.expand().is_none());
+        assert_eq!(msg.errors.len(), 1);
+    }
+}
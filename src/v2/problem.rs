@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+
+use serde::Deserialize;
+
+use crate::types::StatusCode;
+
+/// An error response from a v2 endpoint.
+///
+/// See the [Twitter Developer Documentation][1] for more information.
+///
+/// [1]: https://developer.twitter.com/en/support/twitter-api/error-troubleshooting
+#[derive(Clone, Debug, Deserialize)]
+pub struct Problem {
+    /// The HTTP status code of the response.
+    #[serde(skip)]
+    pub status: StatusCode,
+    /// A short summary of the problem.
+    pub title: String,
+    /// The type of the problem.
+    #[serde(rename = "type")]
+    pub kind: ProblemType,
+    /// A human-readable explanation of the problem.
+    #[serde(default)]
+    pub detail: Option<String>,
+    /// Details about the invalid parameters of the request, if any.
+    #[serde(default)]
+    pub errors: Vec<ProblemMessage>,
+}
+
+/// An error message about the parameters of a request, contained in a `Problem`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct ProblemMessage {
+    pub message: String,
+    /// The offending parameters and their values.
+    #[serde(default)]
+    pub parameters: BTreeMap<String, Vec<String>>,
+}
+
+string_enums! {
+    /// The `type` URI of a `Problem` or a `RuleError`.
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(from = "String")]
+    pub enum ProblemType {
+        InvalidRequest("https://api.twitter.com/2/problems/invalid-request"),
+        InvalidRules("https://api.twitter.com/2/problems/invalid-rules"),
+        DuplicateRules("https://api.twitter.com/2/problems/duplicate-rules"),
+        RuleCap("https://api.twitter.com/2/problems/rule-cap"),
+        NoncompliantRules("https://api.twitter.com/2/problems/noncompliant-rules"),
+        ResourceNotFound("https://api.twitter.com/2/problems/resource-not-found"),
+        NotAuthorizedForResource("https://api.twitter.com/2/problems/not-authorized-for-resource"),
+        ClientForbidden("https://api.twitter.com/2/problems/client-forbidden"),
+        UsageCapped("https://api.twitter.com/2/problems/usage-capped"),
+        StreamingConnection("https://api.twitter.com/2/problems/streaming-connection"),
+        OperationalDisconnect("https://api.twitter.com/2/problems/operational-disconnect");
+        Custom(_),
+    }
+}
+
+impl From<String> for ProblemType {
+    fn from(s: String) -> Self {
+        let known = [
+            ProblemType::InvalidRequest,
+            ProblemType::InvalidRules,
+            ProblemType::DuplicateRules,
+            ProblemType::RuleCap,
+            ProblemType::NoncompliantRules,
+            ProblemType::ResourceNotFound,
+            ProblemType::NotAuthorizedForResource,
+            ProblemType::ClientForbidden,
+            ProblemType::UsageCapped,
+            ProblemType::StreamingConnection,
+            ProblemType::OperationalDisconnect,
+        ];
+        let custom = ProblemType::Custom(s);
+        known
+            .iter()
+            .find(|kind| **kind == custom)
+            .cloned()
+            .unwrap_or(custom)
+    }
+}
+
+impl error::Error for Problem {}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.status, self.title)?;
+        if let Some(ref detail) = self.detail {
+            write!(f, ": {}", detail)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,477 @@
+use std::future::Future;
+
+use http::{Request, Response};
+use http_body::Body;
+use serde::{Deserialize, Serialize};
+use tower_service::Service;
+
+use super::syntax::{self, AccessTier, Expr, ValidationError};
+use super::{send, ProblemType};
+use crate::auth::{Authorizer, BearerToken};
+use crate::error::Error;
+use crate::types::{RequestMethod, Uri};
+use crate::util::*;
+
+/// A client for the rules endpoints of the v2 filtered stream.
+///
+/// See the [Twitter Developer Documentation][1] for more information.
+///
+/// [1]: https://developer.twitter.com/en/docs/twitter-api/tweets/filtered-stream/integrate/build-a-rule
+#[derive(Clone, Debug)]
+pub struct Rules<T = BearerToken> {
+    endpoint: Uri,
+    token: T,
+    pub(super) dry_run: bool,
+}
+
+/// A rule of the filtered stream.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Rule {
+    /// The ID assigned to the rule by Twitter.
+    pub id: String,
+    /// The rule text, e.g. `cat has:images`.
+    pub value: String,
+    /// The label of the rule, which is returned with the Tweets matching it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+/// A rule to be added to the filtered stream.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct NewRule {
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+/// The response of a request to add rules.
+#[derive(Clone, Debug)]
+pub struct AddRulesResponse {
+    /// The rules that were created, or would be created in a dry run.
+    pub rules: Vec<Rule>,
+    pub summary: AddSummary,
+    /// The errors for the rules that were not created.
+    pub errors: Vec<RuleError>,
+}
+
+/// The response of a request to delete rules.
+#[derive(Clone, Debug)]
+pub struct DeleteRulesResponse {
+    pub summary: DeleteSummary,
+    /// The errors for the rules that were not deleted.
+    pub errors: Vec<RuleError>,
+}
+
+/// The number of rules processed by a request to add rules.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+pub struct AddSummary {
+    pub created: u64,
+    pub not_created: u64,
+    pub valid: u64,
+    pub invalid: u64,
+}
+
+/// The number of rules processed by a request to delete rules.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+pub struct DeleteSummary {
+    pub deleted: u64,
+    pub not_deleted: u64,
+}
+
+/// An error for an individual rule that could not be added or deleted.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct RuleError {
+    /// A short summary of the error, e.g. `DuplicateRule`.
+    pub title: String,
+    #[serde(rename = "type")]
+    pub kind: ProblemType,
+    /// The value of the rule, for errors on adding a rule.
+    #[serde(default)]
+    pub value: Option<String>,
+    /// The ID of the rule, for duplicate rules and errors on deleting a rule.
+    #[serde(default)]
+    pub id: Option<String>,
+    /// The reasons why the rule is invalid.
+    #[serde(default)]
+    pub details: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct RulesResponse<S> {
+    #[serde(default)]
+    data: Vec<Rule>,
+    #[serde(default)]
+    meta: Meta<S>,
+    #[serde(default)]
+    errors: Vec<RuleError>,
+}
+
+#[derive(Default, Deserialize)]
+struct Meta<S> {
+    #[serde(default)]
+    summary: S,
+}
+
+#[derive(Serialize)]
+enum RulesRequest<'a> {
+    #[serde(rename = "add")]
+    Add(&'a [NewRule]),
+    #[serde(rename = "delete")]
+    Delete { ids: &'a [&'a str] },
+}
+
+#[derive(oauth::Authorize)]
+struct Query {
+    #[oauth1(skip_if = "not")]
+    dry_run: bool,
+    ids: Option<String>,
+}
+
+impl<T: Authorizer> Rules<T> {
+    /// Create a client for the `/2/tweets/search/stream/rules` endpoint.
+    pub fn new(token: T) -> Self {
+        const URI: &str = "https://api.twitter.com/2/tweets/search/stream/rules";
+        Rules {
+            endpoint: Uri::from_static(URI),
+            token,
+            dry_run: false,
+        }
+    }
+
+    /// Retrieve all the rules of the stream.
+    ///
+    /// # Panics
+    ///
+    /// This will call `<S as Service>::call` without checking for `<S as Service>::poll_ready`
+    /// and may cause a panic if `client` is not ready to send an HTTP request yet.
+    pub fn list<S, ReqB, ResB>(
+        &self,
+        client: S,
+    ) -> impl Future<Output = Result<Vec<Rule>, Error<S::Error>>>
+    where
+        S: Service<Request<ReqB>, Response = Response<ResB>>,
+        ReqB: From<Vec<u8>>,
+        ResB: Body<Error = S::Error>,
+    {
+        self.get_(None, client)
+    }
+
+    /// Retrieve the rules with the given IDs.
+    pub fn get<S, ReqB, ResB>(
+        &self,
+        ids: &[&str],
+        client: S,
+    ) -> impl Future<Output = Result<Vec<Rule>, Error<S::Error>>>
+    where
+        S: Service<Request<ReqB>, Response = Response<ResB>>,
+        ReqB: From<Vec<u8>>,
+        ResB: Body<Error = S::Error>,
+    {
+        self.get_(Some(ids), client)
+    }
+
+    fn get_<S, ReqB, ResB>(
+        &self,
+        ids: Option<&[&str]>,
+        client: S,
+    ) -> impl Future<Output = Result<Vec<Rule>, Error<S::Error>>>
+    where
+        S: Service<Request<ReqB>, Response = Response<ResB>>,
+        ReqB: From<Vec<u8>>,
+        ResB: Body<Error = S::Error>,
+    {
+        let query = encode_params(&Query {
+            dry_run: false,
+            ids: ids.map(|ids| ids.join(",")),
+        });
+        let res = send(
+            &self.token,
+            RequestMethod::GET,
+            &self.endpoint,
+            &query,
+            None,
+            client,
+        );
+        async move {
+            let res: RulesResponse<()> = res.await?;
+            Ok(res.data)
+        }
+    }
+
+    /// Add rules to the stream.
+    ///
+    /// The rules that could not be added are reported in `AddRulesResponse::errors`
+    /// rather than as an `Err`.
+    pub fn add<S, ReqB, ResB>(
+        &self,
+        rules: &[NewRule],
+        client: S,
+    ) -> impl Future<Output = Result<AddRulesResponse, Error<S::Error>>>
+    where
+        S: Service<Request<ReqB>, Response = Response<ResB>>,
+        ReqB: From<Vec<u8>>,
+        ResB: Body<Error = S::Error>,
+    {
+        let res = self.post(&RulesRequest::Add(rules), client);
+        async move {
+            let res: RulesResponse<AddSummary> = res.await?;
+            Ok(AddRulesResponse {
+                rules: res.data,
+                summary: res.meta.summary,
+                errors: res.errors,
+            })
+        }
+    }
+
+    /// Delete the rules with the given IDs from the stream.
+    pub fn delete<S, ReqB, ResB>(
+        &self,
+        ids: &[&str],
+        client: S,
+    ) -> impl Future<Output = Result<DeleteRulesResponse, Error<S::Error>>>
+    where
+        S: Service<Request<ReqB>, Response = Response<ResB>>,
+        ReqB: From<Vec<u8>>,
+        ResB: Body<Error = S::Error>,
+    {
+        let res = self.post(&RulesRequest::Delete { ids }, client);
+        async move {
+            let res: RulesResponse<DeleteSummary> = res.await?;
+            Ok(DeleteRulesResponse {
+                summary: res.meta.summary,
+                errors: res.errors,
+            })
+        }
+    }
+
+    fn post<S, ReqB, ResB, R>(
+        &self,
+        body: &RulesRequest<'_>,
+        client: S,
+    ) -> impl Future<Output = Result<R, Error<S::Error>>>
+    where
+        S: Service<Request<ReqB>, Response = Response<ResB>>,
+        ReqB: From<Vec<u8>>,
+        ResB: Body<Error = S::Error>,
+        R: serde::de::DeserializeOwned,
+    {
+        let query = encode_params(&Query {
+            dry_run: self.dry_run,
+            ids: None,
+        });
+        let body = json::to_vec(body).unwrap();
+        send(
+            &self.token,
+            RequestMethod::POST,
+            &self.endpoint,
+            &query,
+            Some(body),
+            client,
+        )
+    }
+}
+
+impl<T> Rules<T> {
+    /// Reset the API endpoint URI.
+    pub fn endpoint(&mut self, endpoint: Uri) -> &mut Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    /// Reset the token to be used to log into Twitter.
+    pub fn token(&mut self, token: T) -> &mut Self {
+        self.token = token;
+        self
+    }
+
+    /// Set whether to only validate the rules without actually adding or deleting them.
+    pub fn dry_run(&mut self, dry_run: bool) -> &mut Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+impl NewRule {
+    pub fn new<'a>(value: impl Into<String>, tag: impl Into<Option<&'a str>>) -> Self {
+        NewRule {
+            value: value.into(),
+            tag: tag.into().map(Into::into),
+        }
+    }
+
+    /// Parse the value of the rule and check that it can be used with the access tier.
+    /// See `syntax::validate` for more information.
+    pub fn validate(&self, tier: AccessTier) -> Result<Expr, ValidationError> {
+        syntax::validate(&self.value, tier)
+    }
+}
+
+impl From<Rule> for NewRule {
+    fn from(rule: Rule) -> Self {
+        NewRule {
+            value: rule.value,
+            tag: rule.tag,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures_executor::block_on;
+
+    use crate::types::StatusCode;
+    use crate::util::{respond, ServiceFn};
+
+    const URI: &str = "https://api.twitter.com/2/tweets/search/stream/rules";
+
+    #[test]
+    fn list() {
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            assert_eq!(req.method(), RequestMethod::GET);
+            assert_eq!(req.uri(), URI);
+            respond(
+                StatusCode::OK,
+                r#"{
+                    "data": [
+                        {"id": "1", "value": "cat has:images", "tag": "cats with images"},
+                        {"id": "2", "value": "dog"}
+                    ],
+                    "meta": {"sent": "2020-09-01T00:00:00.000Z", "result_count": 2}
+                }"#,
+            )
+        });
+        let rules = block_on(Rules::new(BearerToken::new("AAAA")).list(client)).unwrap();
+        assert_eq!(
+            rules,
+            [
+                Rule {
+                    id: "1".into(),
+                    value: "cat has:images".into(),
+                    tag: Some("cats with images".into()),
+                },
+                Rule {
+                    id: "2".into(),
+                    value: "dog".into(),
+                    tag: None,
+                },
+            ]
+        );
+
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            assert_eq!(req.uri(), &*format!("{}?ids=1%2C2", URI));
+            respond(
+                StatusCode::OK,
+                r#"{"meta": {"sent": "", "result_count": 0}}"#,
+            )
+        });
+        let token = BearerToken::new("AAAA");
+        let rules = block_on(Rules::new(token).get(&["1", "2"], client)).unwrap();
+        assert!(rules.is_empty());
+    }
+
+    #[test]
+    fn add() {
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            assert_eq!(req.method(), RequestMethod::POST);
+            assert_eq!(req.uri(), &*format!("{}?dry_run=true", URI));
+            assert_eq!(req.headers()["content-type"], "application/json");
+            let body: json::Value = json::from_slice(req.body()).unwrap();
+            assert_eq!(
+                body,
+                json::json!({
+                    "add": [{"value": "cat has:images", "tag": "cats"}, {"value": "dog"}],
+                }),
+            );
+            respond(
+                StatusCode::OK,
+                r#"{
+                    "data": [{"value": "cat has:images", "tag": "cats", "id": "3"}],
+                    "meta": {
+                        "sent": "2020-09-01T00:00:00.000Z",
+                        "summary": {"created": 1, "not_created": 1, "valid": 1, "invalid": 1}
+                    },
+                    "errors": [{
+                        "value": "dog",
+                        "id": "2",
+                        "title": "DuplicateRule",
+                        "type": "https://api.twitter.com/2/problems/duplicate-rules"
+                    }]
+                }"#,
+            )
+        });
+
+        let rules = [
+            NewRule::new("cat has:images", "cats"),
+            NewRule::new("dog", None),
+        ];
+        let res = Rules::new(BearerToken::new("AAAA"))
+            .dry_run(true)
+            .add(&rules, client);
+        let res = block_on(res).unwrap();
+        assert_eq!(res.rules.len(), 1);
+        assert_eq!(res.rules[0].id, "3");
+        assert_eq!(
+            res.summary,
+            AddSummary {
+                created: 1,
+                not_created: 1,
+                valid: 1,
+                invalid: 1,
+            }
+        );
+        assert_eq!(res.errors[0].kind, ProblemType::DuplicateRules);
+        assert_eq!(res.errors[0].id.as_ref().unwrap(), "2");
+    }
+
+    #[test]
+    fn delete() {
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            assert_eq!(req.uri(), URI);
+            let body: json::Value = json::from_slice(req.body()).unwrap();
+            assert_eq!(body, json::json!({"delete": {"ids": ["1", "2"]}}));
+            respond(
+                StatusCode::OK,
+                r#"{"meta": {"sent": "", "summary": {"deleted": 2, "not_deleted": 0}}}"#,
+            )
+        });
+        let res = Rules::new(BearerToken::new("AAAA")).delete(&["1", "2"], client);
+        let res = block_on(res).unwrap();
+        assert_eq!(
+            res.summary,
+            DeleteSummary {
+                deleted: 2,
+                not_deleted: 0,
+            }
+        );
+        assert!(res.errors.is_empty());
+    }
+
+    #[test]
+    fn invalid_request() {
+        let client = ServiceFn(|_: Request<Vec<u8>>| {
+            respond(
+                StatusCode::BAD_REQUEST,
+                r#"{
+                    "errors": [{
+                        "parameters": {"ids": ["a"]},
+                        "message": "The `ids` query parameter value [a] is not valid"
+                    }],
+                    "title": "Invalid Request",
+                    "detail": "One or more parameters to your request was invalid.",
+                    "type": "https://api.twitter.com/2/problems/invalid-request"
+                }"#,
+            )
+        });
+        let res = block_on(Rules::new(BearerToken::new("AAAA")).get(&["a"], client));
+        match res {
+            Err(Error::Api(problem)) => {
+                assert_eq!(problem.status, StatusCode::BAD_REQUEST);
+                assert_eq!(problem.kind, ProblemType::InvalidRequest);
+                assert_eq!(problem.errors[0].parameters["ids"], ["a"]);
+            }
+            res => panic!("{:?}", res),
+        }
+    }
+}
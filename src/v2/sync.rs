@@ -0,0 +1,384 @@
+use std::collections::HashSet;
+
+use http::{Request, Response};
+use http_body::Body;
+use tower_service::Service;
+
+use super::{NewRule, Rule, RuleError, Rules};
+use crate::auth::Authorizer;
+use crate::error::Error;
+
+/// The changes needed to turn a set of rules into a desired one.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RuleDiff {
+    /// The desired rules that do not exist yet.
+    pub add: Vec<NewRule>,
+    /// The existing rules that are not desired, including duplicates of a desired rule.
+    pub delete: Vec<Rule>,
+    /// The existing rules that are desired.
+    pub unchanged: Vec<Rule>,
+}
+
+/// The result of `Rules::sync`.
+#[derive(Clone, Debug, Default)]
+pub struct SyncReport {
+    /// The rules that were added, as returned by the API.
+    pub added: Vec<Rule>,
+    /// The rules that were deleted.
+    pub deleted: Vec<Rule>,
+    /// The rules that were already in place.
+    pub unchanged: Vec<Rule>,
+    /// The errors for the rules that could not be added or deleted.
+    pub errors: Vec<RuleError>,
+    /// The rules that were left out of a dry run because they replace a rule with
+    /// the same value (e.g. with a changed tag). They cannot be validated until the old rule
+    /// has actually been deleted, and are expected to be added in a real run.
+    pub unvalidated: Vec<NewRule>,
+    /// Whether the changes were only validated and not actually applied.
+    pub dry_run: bool,
+}
+
+impl RuleDiff {
+    /// Compute the minimal set of changes to turn `current` into `desired`.
+    ///
+    /// Rules are compared by both their value and tag, so a rule whose tag is changed
+    /// is deleted and added again. The API does not allow two rules with the same value,
+    /// so only the first of the rules in `desired` with the same value is taken.
+    pub fn new(current: &[Rule], desired: &[NewRule]) -> Self {
+        let mut diff = RuleDiff::default();
+
+        let mut values = HashSet::new();
+        let desired: Vec<&NewRule> = desired
+            .iter()
+            .filter(|rule| values.insert(&*rule.value))
+            .collect();
+
+        let wanted: HashSet<(&str, Option<&str>)> = desired.iter().map(|rule| key(rule)).collect();
+        let mut kept = HashSet::new();
+        for rule in current {
+            let k = (&*rule.value, rule.tag.as_deref());
+            if wanted.contains(&k) && kept.insert(k) {
+                diff.unchanged.push(rule.clone());
+            } else {
+                diff.delete.push(rule.clone());
+            }
+        }
+
+        for rule in desired {
+            if kept.insert(key(rule)) {
+                diff.add.push((*rule).clone());
+            }
+        }
+
+        diff
+    }
+
+    /// Returns `true` if there is nothing to add or delete.
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty() && self.delete.is_empty()
+    }
+}
+
+fn key(rule: &NewRule) -> (&str, Option<&str>) {
+    (&rule.value, rule.tag.as_deref())
+}
+
+impl<T: Authorizer> Rules<T> {
+    /// Make the rules of the stream match `desired`.
+    ///
+    /// This fetches the current rules, deletes the ones that are not in `desired` and
+    /// adds the missing ones, using `client` for each request. Requests with nothing to do
+    /// are skipped. If `dry_run` is set, the changes are only validated by the API,
+    /// except for the rules reported as `unvalidated`.
+    ///
+    /// # Panics
+    ///
+    /// This will call `<S as Service>::call` without checking for `<S as Service>::poll_ready`
+    /// and may cause a panic if `client` is not ready to send an HTTP request yet.
+    pub async fn sync<S, ReqB, ResB>(
+        &self,
+        desired: &[NewRule],
+        mut client: S,
+    ) -> Result<SyncReport, Error<S::Error>>
+    where
+        S: Service<Request<ReqB>, Response = Response<ResB>>,
+        ReqB: From<Vec<u8>>,
+        ResB: Body<Error = S::Error>,
+    {
+        let current = self.list(&mut client).await?;
+        let diff = RuleDiff::new(&current, desired);
+
+        let mut report = SyncReport {
+            unchanged: diff.unchanged,
+            dry_run: self.dry_run,
+            ..SyncReport::default()
+        };
+
+        // Delete first so that a rule whose tag has changed can be added again.
+        if !diff.delete.is_empty() {
+            let ids: Vec<&str> = diff.delete.iter().map(|rule| &*rule.id).collect();
+            let res = self.delete(&ids, &mut client).await?;
+            let failed: HashSet<&str> = res.errors.iter().filter_map(|e| e.id.as_deref()).collect();
+            report.deleted = diff
+                .delete
+                .iter()
+                .filter(|rule| !failed.contains(&*rule.id))
+                .cloned()
+                .collect();
+            report.errors.extend(res.errors);
+        }
+
+        let mut add = diff.add;
+        if self.dry_run {
+            // The deletion is not applied, so the API would reject a rule replacing one of
+            // the deleted rules as a duplicate.
+            let deleted: HashSet<&str> = diff.delete.iter().map(|rule| &*rule.value).collect();
+            let (unvalidated, rest) = add
+                .into_iter()
+                .partition(|rule| deleted.contains(&*rule.value));
+            report.unvalidated = unvalidated;
+            add = rest;
+        }
+
+        if !add.is_empty() {
+            let res = self.add(&add, &mut client).await?;
+            report.added = res.rules;
+            report.errors.extend(res.errors);
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures_executor::block_on;
+
+    use crate::auth::BearerToken;
+    use crate::types::{RequestMethod, StatusCode};
+    use crate::util::{respond, ServiceFn};
+    use crate::v2::ProblemType;
+
+    fn rule(id: &str, value: &str, tag: Option<&str>) -> Rule {
+        Rule {
+            id: id.into(),
+            value: value.into(),
+            tag: tag.map(Into::into),
+        }
+    }
+
+    fn current() -> Vec<Rule> {
+        vec![
+            rule("1", "cat", Some("cats")),
+            rule("2", "dog", None),
+            rule("3", "dog", None),
+            rule("4", "bird", Some("b")),
+        ]
+    }
+
+    fn desired() -> Vec<NewRule> {
+        vec![
+            NewRule::new("cat", "cats"),
+            NewRule::new("dog", None),
+            NewRule::new("bird", "birds"),
+            NewRule::new("fish", None),
+            NewRule::new("fish", None),
+        ]
+    }
+
+    #[test]
+    fn diff() {
+        let diff = RuleDiff::new(&current(), &desired());
+        assert_eq!(
+            diff,
+            RuleDiff {
+                add: vec![NewRule::new("bird", "birds"), NewRule::new("fish", None)],
+                delete: vec![rule("3", "dog", None), rule("4", "bird", Some("b"))],
+                unchanged: vec![rule("1", "cat", Some("cats")), rule("2", "dog", None)],
+            }
+        );
+        assert!(!diff.is_empty());
+
+        let current = [rule("1", "cat", Some("cats"))];
+        assert!(RuleDiff::new(&current, &[NewRule::new("cat", "cats")]).is_empty());
+
+        // Only the first rule with the same value is taken.
+        let desired = [NewRule::new("cat", "c"), NewRule::new("cat", "cats")];
+        let diff = RuleDiff::new(&current, &desired);
+        assert_eq!(diff.add, [NewRule::new("cat", "c")]);
+        assert_eq!(diff.delete, current);
+        let diff = RuleDiff::new(
+            &current,
+            &[NewRule::new("cat", "cats"), NewRule::new("cat", None)],
+        );
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn sync() {
+        let mut requests = Vec::new();
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            let body = if req.body().is_empty() {
+                json::Value::Null
+            } else {
+                json::from_slice(req.body()).unwrap()
+            };
+            requests.push((req.method().clone(), req.uri().to_string(), body));
+            match requests.len() {
+                1 => respond(
+                    StatusCode::OK,
+                    r#"{"data": [
+                        {"id": "1", "value": "cat", "tag": "cats"},
+                        {"id": "2", "value": "dog"},
+                        {"id": "3", "value": "dog"},
+                        {"id": "4", "value": "bird", "tag": "b"}
+                    ]}"#,
+                ),
+                2 => respond(
+                    StatusCode::OK,
+                    r#"{
+                        "meta": {"summary": {"deleted": 2, "not_deleted": 0}}
+                    }"#,
+                ),
+                3 => respond(
+                    StatusCode::OK,
+                    r#"{
+                        "data": [{"id": "5", "value": "bird", "tag": "birds"}],
+                        "meta": {
+                            "summary": {"created": 1, "not_created": 1, "valid": 1, "invalid": 1}
+                        },
+                        "errors": [{
+                            "value": "fish",
+                            "details": ["Rules must contain a non-stopword."],
+                            "title": "UnprocessableEntity",
+                            "type": "https://api.twitter.com/2/problems/invalid-rules"
+                        }]
+                    }"#,
+                ),
+                _ => panic!("unexpected request"),
+            }
+        });
+
+        let rules = Rules::new(BearerToken::new("AAAA"));
+        let desired = desired();
+        let report = block_on(rules.sync(&desired, client)).unwrap();
+
+        assert!(!report.dry_run);
+        assert_eq!(report.added, [rule("5", "bird", Some("birds"))]);
+        assert_eq!(
+            report.deleted,
+            [rule("3", "dog", None), rule("4", "bird", Some("b"))]
+        );
+        assert_eq!(report.unchanged.len(), 2);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].kind, ProblemType::InvalidRules);
+
+        const URI: &str = "https://api.twitter.com/2/tweets/search/stream/rules";
+        assert_eq!(
+            requests,
+            [
+                (RequestMethod::GET, URI.to_string(), json::Value::Null),
+                (
+                    RequestMethod::POST,
+                    URI.to_string(),
+                    json::json!({"delete": {"ids": ["3", "4"]}}),
+                ),
+                (
+                    RequestMethod::POST,
+                    URI.to_string(),
+                    json::json!({"add": [{"value": "bird", "tag": "birds"}, {"value": "fish"}]}),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn sync_dry_run() {
+        let mut uris = Vec::new();
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            uris.push(req.uri().to_string());
+            if req.method() == RequestMethod::GET {
+                respond(StatusCode::OK, r#"{"data": [{"id": "1", "value": "dog"}]}"#)
+            } else {
+                respond(
+                    StatusCode::OK,
+                    r#"{"meta": {"summary": {"deleted": 0, "not_deleted": 1}}, "errors": [{
+                        "id": "1",
+                        "title": "Rule not found",
+                        "type": "https://api.twitter.com/2/problems/resource-not-found"
+                    }]}"#,
+                )
+            }
+        });
+
+        let mut rules = Rules::new(BearerToken::new("AAAA"));
+        rules.dry_run(true);
+        let report = block_on(rules.sync(&[], client)).unwrap();
+
+        assert!(report.dry_run);
+        assert!(report.deleted.is_empty());
+        assert_eq!(report.errors[0].kind, ProblemType::ResourceNotFound);
+        assert_eq!(
+            uris,
+            [
+                "https://api.twitter.com/2/tweets/search/stream/rules",
+                "https://api.twitter.com/2/tweets/search/stream/rules?dry_run=true",
+            ]
+        );
+    }
+
+    #[test]
+    fn sync_dry_run_changed_tag() {
+        let mut requests = Vec::new();
+        let client = ServiceFn(|req: Request<Vec<u8>>| {
+            requests.push((req.uri().to_string(), req.body().clone()));
+            match requests.len() {
+                1 => respond(
+                    StatusCode::OK,
+                    r#"{"data": [{"id": "1", "value": "cat", "tag": "c"}]}"#,
+                ),
+                2 => respond(
+                    StatusCode::OK,
+                    r#"{"meta": {"summary": {"deleted": 1, "not_deleted": 0}}}"#,
+                ),
+                3 => respond(
+                    StatusCode::OK,
+                    r#"{
+                        "data": [{"id": "2", "value": "dog"}],
+                        "meta": {
+                            "summary": {"created": 1, "not_created": 0, "valid": 1, "invalid": 0}
+                        }
+                    }"#,
+                ),
+                _ => panic!("unexpected request"),
+            }
+        });
+
+        let mut rules = Rules::new(BearerToken::new("AAAA"));
+        rules.dry_run(true);
+        let desired = [NewRule::new("cat", "cats"), NewRule::new("dog", None)];
+        let report = block_on(rules.sync(&desired, client)).unwrap();
+
+        assert!(report.dry_run);
+        assert!(report.errors.is_empty());
+        assert_eq!(report.deleted, [rule("1", "cat", Some("c"))]);
+        assert_eq!(report.added, [rule("2", "dog", None)]);
+        assert_eq!(report.unvalidated, [NewRule::new("cat", "cats")]);
+
+        const URI: &str = "https://api.twitter.com/2/tweets/search/stream/rules?dry_run=true";
+        let body = |body: &[u8]| json::from_slice::<json::Value>(body).unwrap();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1].0, URI);
+        assert_eq!(
+            body(&requests[1].1),
+            json::json!({"delete": {"ids": ["1"]}})
+        );
+        assert_eq!(requests[2].0, URI);
+        assert_eq!(
+            body(&requests[2].1),
+            json::json!({"add": [{"value": "dog"}]})
+        );
+    }
+}
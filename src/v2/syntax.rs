@@ -0,0 +1,997 @@
+//! Parser, validator and pretty-printer for the rule language of the v2 filtered stream.
+//!
+//! See the [Twitter Developer Documentation][1] for the syntax of the rules.
+//!
+//! [1]: https://developer.twitter.com/en/docs/twitter-api/tweets/filtered-stream/integrate/build-a-rule
+//!
+//! ## Example
+//!
+//! ```rust
+//! use twitter_stream::v2::syntax::{self, AccessTier, Expr};
+//!
+//! let expr: Expr = "(cat OR dog) has:images -is:retweet".parse().unwrap();
+//! assert_eq!(expr.to_string(), "(cat OR dog) has:images -is:retweet");
+//!
+//! // `bio:` is only available with Academic Research access.
+//! assert!(syntax::validate("bio:developer rust", AccessTier::Essential).is_err());
+//! assert!(syntax::validate("bio:developer rust", AccessTier::AcademicResearch).is_ok());
+//! ```
+
+use std::error;
+use std::fmt::{self, Display, Formatter, Write};
+use std::str::FromStr;
+
+/// A parsed rule.
+///
+/// The `Display` implementation prints the rule in a canonical form which parses back
+/// into the same `Expr`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    /// Terms separated by spaces, all of which must match.
+    And(Vec<Expr>),
+    /// Terms separated by `OR`, any of which must match.
+    Or(Vec<Expr>),
+    /// A term prefixed with `-`, which must not match.
+    Not(Box<Expr>),
+    /// A keyword, e.g. `cat`.
+    Keyword(String),
+    /// An exact phrase in double quotes, e.g. `"happy birthday"`.
+    Phrase(String),
+    /// A hashtag without the leading `#`.
+    Hashtag(String),
+    /// A mention without the leading `@`.
+    Mention(String),
+    /// A cashtag without the leading `$`.
+    Cashtag(String),
+    /// An operator with its value, e.g. `from:TwitterDev`.
+    Operator(Operator, String),
+    /// `point_radius:[longitude latitude radius]`.
+    PointRadius(PointRadius),
+    /// `bounding_box:[west_long south_lat east_long north_lat]`.
+    BoundingBox(BoundingBox),
+}
+
+/// An operator of the form `name:value`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operator {
+    From,
+    To,
+    Url,
+    RetweetsOf,
+    Context,
+    Entity,
+    ConversationId,
+    Bio,
+    BioName,
+    BioLocation,
+    Place,
+    PlaceCountry,
+    Is,
+    Has,
+    Lang,
+    Sample,
+}
+
+/// The value of a `point_radius` operator.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointRadius {
+    pub longitude: f64,
+    pub latitude: f64,
+    pub radius: f64,
+    pub unit: DistanceUnit,
+}
+
+/// The unit of the radius of a `point_radius` operator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DistanceUnit {
+    Miles,
+    Kilometers,
+}
+
+/// The value of a `bounding_box` operator.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub west: f64,
+    pub south: f64,
+    pub east: f64,
+    pub north: f64,
+}
+
+/// The level of access to the Twitter API, which determines the limits on the rules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AccessTier {
+    Essential,
+    Elevated,
+    AcademicResearch,
+}
+
+/// An error from parsing a rule.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    offset: usize,
+}
+
+/// The cause of a `ParseError`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A term was expected, e.g. at the end of the rule after `OR`.
+    ExpectedTerm,
+    /// An opening parenthesis is not closed.
+    UnclosedParen,
+    /// A closing parenthesis does not have a matching opening parenthesis.
+    UnmatchedParen,
+    /// A double quote is not closed.
+    UnterminatedQuote,
+    /// The name of the operator is not known. Contains the name.
+    UnknownOperator(String),
+    /// The value of the operator is not valid. Contains the name of the operator.
+    InvalidValue(String),
+}
+
+/// An error from validating a rule.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationError {
+    /// The rule could not be parsed.
+    Parse(ParseError),
+    /// The rule is longer than allowed for the access tier.
+    TooLong { len: usize, max: usize },
+    /// The operator is not available for the access tier.
+    ///
+    /// The operator is given by name (e.g. `place_country`), except for cashtags (`$`) and
+    /// the values of `has:` that are only available with advanced operators (e.g. `has:geo`).
+    Unavailable {
+        operator: &'static str,
+        tier: AccessTier,
+    },
+    /// The rule does not contain a positive term that can be used on its own,
+    /// e.g. it only consists of `has:` operators or negated terms.
+    MissingStandaloneTerm,
+    /// The operator (given by name and value) can only be used in negated form.
+    MustBeNegated(String),
+    /// The area of a `point_radius` or `bounding_box` operator is out of range.
+    AreaOutOfRange,
+}
+
+const IS_VALUES: &[&str] = &["retweet", "reply", "quote", "verified", "nullcast"];
+const HAS_VALUES: &[&str] = &[
+    "hashtags", "cashtags", "links", "mentions", "media", "images", "videos", "geo",
+];
+
+impl Expr {
+    /// Parse a rule.
+    pub fn parse(rule: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser { s: rule, pos: 0 };
+        let expr = parser.or_expr()?;
+        parser.skip_ws();
+        if parser.pos < rule.len() {
+            // `or_expr` only stops before a closing parenthesis.
+            return Err(parser.error(ParseErrorKind::UnmatchedParen));
+        }
+        Ok(expr)
+    }
+
+    /// Check that the rule can be used with the access tier.
+    ///
+    /// This does not check the length of the rule, which is done by `validate`.
+    pub fn validate(&self, tier: AccessTier) -> Result<(), ValidationError> {
+        self.check(tier)?;
+        if !self.is_standalone() {
+            return Err(ValidationError::MissingStandaloneTerm);
+        }
+        Ok(())
+    }
+
+    fn check(&self, tier: AccessTier) -> Result<(), ValidationError> {
+        let (name, advanced) = match *self {
+            Expr::And(ref items) | Expr::Or(ref items) => {
+                return items.iter().try_for_each(|e| e.check(tier));
+            }
+            Expr::Not(ref e) => {
+                return match **e {
+                    Expr::Operator(Operator::Is, ref value) if value == "nullcast" => Ok(()),
+                    _ => e.check(tier),
+                };
+            }
+            Expr::Cashtag(_) => ("$", true),
+            Expr::Operator(Operator::Has, ref value) if value == "cashtags" => {
+                ("has:cashtags", true)
+            }
+            Expr::Operator(Operator::Has, ref value) if value == "geo" => ("has:geo", true),
+            Expr::Operator(op, ref value) => {
+                if op == Operator::Is && value == "nullcast" {
+                    return Err(ValidationError::MustBeNegated(self.to_string()));
+                }
+                (op.name(), op.is_advanced())
+            }
+            Expr::PointRadius(ref p) => {
+                let max = match p.unit {
+                    DistanceUnit::Miles => 25.,
+                    DistanceUnit::Kilometers => 40.,
+                };
+                if !in_range(p.longitude, p.latitude) || p.radius <= 0. || p.radius > max {
+                    return Err(ValidationError::AreaOutOfRange);
+                }
+                ("point_radius", true)
+            }
+            Expr::BoundingBox(ref b) => {
+                // Approximate the size of the box in miles.
+                let height = (b.north - b.south) * 69.;
+                let mid = (b.north + b.south) / 2.;
+                let width = (b.east - b.west) * 69. * mid.to_radians().cos();
+                if !in_range(b.west, b.south)
+                    || !in_range(b.east, b.north)
+                    || height <= 0.
+                    || height > 25.
+                    || width <= 0.
+                    || width > 25.
+                {
+                    return Err(ValidationError::AreaOutOfRange);
+                }
+                ("bounding_box", true)
+            }
+            _ => return Ok(()),
+        };
+
+        if advanced && tier < AccessTier::AcademicResearch {
+            return Err(ValidationError::Unavailable {
+                operator: name,
+                tier,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Whether the expression can match Tweets on its own, i.e. it is not made up of
+    /// operators that require a conjunction or negated terms only.
+    fn is_standalone(&self) -> bool {
+        match *self {
+            Expr::And(ref items) => items.iter().any(Expr::is_standalone),
+            Expr::Or(ref items) => items.iter().all(Expr::is_standalone),
+            Expr::Not(_) => false,
+            Expr::Operator(op, _) => !op.requires_conjunction(),
+            _ => true,
+        }
+    }
+}
+
+/// Parse `rule` and check that it can be used with the access tier.
+pub fn validate(rule: &str, tier: AccessTier) -> Result<Expr, ValidationError> {
+    let len = rule.chars().count();
+    let max = tier.max_rule_len();
+    if len > max {
+        return Err(ValidationError::TooLong { len, max });
+    }
+
+    let expr = Expr::parse(rule).map_err(ValidationError::Parse)?;
+    expr.validate(tier)?;
+    Ok(expr)
+}
+
+fn in_range(longitude: f64, latitude: f64) -> bool {
+    (-180.0..=180.).contains(&longitude) && (-90.0..=90.).contains(&latitude)
+}
+
+impl Operator {
+    /// Returns the name of the operator as written in a rule, without the colon.
+    pub fn name(self) -> &'static str {
+        match self {
+            Operator::From => "from",
+            Operator::To => "to",
+            Operator::Url => "url",
+            Operator::RetweetsOf => "retweets_of",
+            Operator::Context => "context",
+            Operator::Entity => "entity",
+            Operator::ConversationId => "conversation_id",
+            Operator::Bio => "bio",
+            Operator::BioName => "bio_name",
+            Operator::BioLocation => "bio_location",
+            Operator::Place => "place",
+            Operator::PlaceCountry => "place_country",
+            Operator::Is => "is",
+            Operator::Has => "has",
+            Operator::Lang => "lang",
+            Operator::Sample => "sample",
+        }
+    }
+
+    /// Returns the operator with the given name, if any.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let op = match name {
+            "from" => Operator::From,
+            "to" => Operator::To,
+            "url" => Operator::Url,
+            "retweets_of" => Operator::RetweetsOf,
+            "context" => Operator::Context,
+            "entity" => Operator::Entity,
+            "conversation_id" => Operator::ConversationId,
+            "bio" => Operator::Bio,
+            "bio_name" => Operator::BioName,
+            "bio_location" => Operator::BioLocation,
+            "place" => Operator::Place,
+            "place_country" => Operator::PlaceCountry,
+            "is" => Operator::Is,
+            "has" => Operator::Has,
+            "lang" => Operator::Lang,
+            "sample" => Operator::Sample,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// Whether the operator can only be used together with another, standalone, term.
+    pub fn requires_conjunction(self) -> bool {
+        matches!(
+            self,
+            Operator::Is | Operator::Has | Operator::Lang | Operator::Sample
+        )
+    }
+
+    /// Whether the operator is an advanced operator, which requires Academic Research access.
+    /// `point_radius`, `bounding_box`, cashtags, `has:cashtags` and `has:geo` are advanced
+    /// operators too.
+    pub fn is_advanced(self) -> bool {
+        matches!(
+            self,
+            Operator::Bio
+                | Operator::BioName
+                | Operator::BioLocation
+                | Operator::Place
+                | Operator::PlaceCountry
+        )
+    }
+}
+
+impl AccessTier {
+    /// Returns the maximum length of a rule in characters.
+    pub fn max_rule_len(self) -> usize {
+        match self {
+            AccessTier::Essential | AccessTier::Elevated => 512,
+            AccessTier::AcademicResearch => 1024,
+        }
+    }
+
+    /// Returns the maximum number of rules of a stream.
+    pub fn max_rules(self) -> usize {
+        match self {
+            AccessTier::Essential => 5,
+            AccessTier::Elevated => 25,
+            AccessTier::AcademicResearch => 1000,
+        }
+    }
+}
+
+impl ParseError {
+    /// Returns the cause of the error.
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+
+    /// Returns the byte offset in the rule where the error was found.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn or_expr(&mut self) -> Result<Expr, ParseError> {
+        let mut items = vec![self.and_expr()?];
+        while self.at_or() {
+            self.pos += 2;
+            items.push(self.and_expr()?);
+        }
+        Ok(one_or(items, Expr::Or))
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, ParseError> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_ws();
+            match self.peek() {
+                None | Some(')') => break,
+                Some(_) if self.at_or() => break,
+                Some(_) => items.push(self.unary()?),
+            }
+        }
+        if items.is_empty() {
+            return Err(self.error(ParseErrorKind::ExpectedTerm));
+        }
+        Ok(one_or(items, Expr::And))
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        let rest = self.rest();
+        if rest.starts_with('-') && rest[1..].starts_with(|c: char| !c.is_whitespace()) {
+            self.pos += 1;
+            self.primary().map(|e| Expr::Not(Box::new(e)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Some('(') => {
+                let start = self.pos;
+                self.pos += 1;
+                let expr = self.or_expr()?;
+                if self.peek() != Some(')') {
+                    return Err(ParseError {
+                        kind: ParseErrorKind::UnclosedParen,
+                        offset: start,
+                    });
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some('"') => self.quoted().map(Expr::Phrase),
+            Some(')') | None => Err(self.error(ParseErrorKind::ExpectedTerm)),
+            Some(_) => self.word(),
+        }
+    }
+
+    fn word(&mut self) -> Result<Expr, ParseError> {
+        let start = self.pos;
+        let len = self.rest().find(is_delimiter).unwrap_or(self.rest().len());
+        let word = &self.s[start..start + len];
+
+        if let Some(i) = word.find(':') {
+            let name = &word[..i];
+            if !name.is_empty() && name.bytes().all(|b| b.is_ascii_lowercase() || b == b'_') {
+                self.pos += i + 1;
+                return self.operator(name, start);
+            }
+        }
+
+        self.pos += len;
+        let expr = match word.chars().next() {
+            Some('#') if len > 1 => Expr::Hashtag(word[1..].to_string()),
+            Some('@') if len > 1 => Expr::Mention(word[1..].to_string()),
+            Some('$') if len > 1 => Expr::Cashtag(word[1..].to_string()),
+            _ => Expr::Keyword(word.to_string()),
+        };
+        Ok(expr)
+    }
+
+    fn operator(&mut self, name: &str, start: usize) -> Result<Expr, ParseError> {
+        let invalid = || ParseError {
+            kind: ParseErrorKind::InvalidValue(name.to_string()),
+            offset: start,
+        };
+
+        let (value, bracketed) = match self.peek() {
+            Some('"') => (self.quoted()?, false),
+            Some('[') => {
+                let len = self.rest().find(']').ok_or_else(invalid)?;
+                let value = self.s[self.pos + 1..self.pos + len].to_string();
+                self.pos += len + 1;
+                (value, true)
+            }
+            _ => {
+                let len = self.rest().find(is_delimiter).unwrap_or(self.rest().len());
+                let value = self.s[self.pos..self.pos + len].to_string();
+                self.pos += len;
+                (value, false)
+            }
+        };
+
+        let numbers = |n: usize| {
+            let nums = value
+                .split_whitespace()
+                .map(|v| v.parse::<f64>().ok().filter(|v| v.is_finite()))
+                .collect::<Option<Vec<_>>>()
+                .filter(|nums| nums.len() == n && bracketed);
+            nums.ok_or_else(invalid)
+        };
+
+        match name {
+            "point_radius" => {
+                let mut parts: Vec<&str> = value.split_whitespace().collect();
+                let radius = parts.pop().filter(|_| bracketed && parts.len() == 2);
+                let radius = radius.ok_or_else(invalid)?;
+                let (radius, unit) = if let Some(r) = radius.strip_suffix("mi") {
+                    (r, DistanceUnit::Miles)
+                } else if let Some(r) = radius.strip_suffix("km") {
+                    (r, DistanceUnit::Kilometers)
+                } else {
+                    return Err(invalid());
+                };
+                let radius = radius.parse::<f64>().map_err(|_| invalid())?;
+                let coords = parts
+                    .iter()
+                    .map(|v| v.parse::<f64>().ok().filter(|v| v.is_finite()))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(invalid)?;
+                Ok(Expr::PointRadius(PointRadius {
+                    longitude: coords[0],
+                    latitude: coords[1],
+                    radius,
+                    unit,
+                }))
+            }
+            "bounding_box" => {
+                let nums = numbers(4)?;
+                Ok(Expr::BoundingBox(BoundingBox {
+                    west: nums[0],
+                    south: nums[1],
+                    east: nums[2],
+                    north: nums[3],
+                }))
+            }
+            _ => {
+                let op = Operator::from_name(name).ok_or_else(|| ParseError {
+                    kind: ParseErrorKind::UnknownOperator(name.to_string()),
+                    offset: start,
+                })?;
+                let valid = !bracketed
+                    && !value.is_empty()
+                    && match op {
+                        Operator::Is => IS_VALUES.contains(&&*value),
+                        Operator::Has => HAS_VALUES.contains(&&*value),
+                        Operator::Sample => match value.parse::<u8>() {
+                            Ok(n) => (1..=100).contains(&n),
+                            Err(_) => false,
+                        },
+                        _ => true,
+                    };
+                if !valid {
+                    return Err(invalid());
+                }
+                Ok(Expr::Operator(op, value))
+            }
+        }
+    }
+
+    fn quoted(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        let mut ret = String::new();
+        let mut chars = self.rest()[1..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 2;
+                    return Ok(ret);
+                }
+                '\\' => match chars.next() {
+                    Some((_, c)) => ret.push(c),
+                    None => break,
+                },
+                c => ret.push(c),
+            }
+        }
+        Err(ParseError {
+            kind: ParseErrorKind::UnterminatedQuote,
+            offset: start,
+        })
+    }
+
+    fn at_or(&self) -> bool {
+        let rest = self.rest();
+        rest.starts_with("OR")
+            && match rest[2..].chars().next() {
+                None => true,
+                Some(c) => c.is_whitespace() || c == '(' || c == '"',
+            }
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            kind,
+            offset: self.pos,
+        }
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || c == '(' || c == ')' || c == '"'
+}
+
+fn one_or(mut items: Vec<Expr>, f: fn(Vec<Expr>) -> Expr) -> Expr {
+    if items.len() == 1 {
+        items.pop().unwrap()
+    } else {
+        f(items)
+    }
+}
+
+impl FromStr for Expr {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        Expr::parse(s)
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Expr::And(ref items) => fmt_items(items, true, f),
+            Expr::Or(ref items) => fmt_items(items, false, f),
+            Expr::Not(ref e) => match **e {
+                Expr::And(_) | Expr::Or(_) => write!(f, "-({})", e),
+                _ => write!(f, "-{}", e),
+            },
+            Expr::Keyword(ref s) => f.write_str(s),
+            Expr::Phrase(ref s) => fmt_quoted(s, f),
+            Expr::Hashtag(ref s) => write!(f, "#{}", s),
+            Expr::Mention(ref s) => write!(f, "@{}", s),
+            Expr::Cashtag(ref s) => write!(f, "${}", s),
+            Expr::Operator(op, ref value) => {
+                write!(f, "{}:", op.name())?;
+                let quote = value.is_empty()
+                    || value.starts_with('[')
+                    || value.contains(|c| is_delimiter(c) || c == '\\');
+                if quote {
+                    fmt_quoted(value, f)
+                } else {
+                    f.write_str(value)
+                }
+            }
+            Expr::PointRadius(ref p) => {
+                let unit = match p.unit {
+                    DistanceUnit::Miles => "mi",
+                    DistanceUnit::Kilometers => "km",
+                };
+                write!(
+                    f,
+                    "point_radius:[{} {} {}{}]",
+                    p.longitude, p.latitude, p.radius, unit
+                )
+            }
+            Expr::BoundingBox(ref b) => write!(
+                f,
+                "bounding_box:[{} {} {} {}]",
+                b.west, b.south, b.east, b.north
+            ),
+        }
+    }
+}
+
+fn fmt_items(items: &[Expr], and: bool, f: &mut Formatter<'_>) -> fmt::Result {
+    for (i, e) in items.iter().enumerate() {
+        if i > 0 {
+            f.write_str(if and { " " } else { " OR " })?;
+        }
+        // `AND` binds tighter than `OR`, so an `OR` group inside an `AND` group needs
+        // parentheses. Nested groups of the same kind are kept as they are.
+        let paren = match *e {
+            Expr::And(_) => and,
+            Expr::Or(_) => true,
+            _ => false,
+        };
+        if paren {
+            write!(f, "({})", e)?;
+        } else {
+            Display::fmt(e, f)?;
+        }
+    }
+    Ok(())
+}
+
+fn fmt_quoted(s: &str, f: &mut Formatter<'_>) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    f.write_char('"')
+}
+
+impl Display for AccessTier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            AccessTier::Essential => "Essential",
+            AccessTier::Elevated => "Elevated",
+            AccessTier::AcademicResearch => "Academic Research",
+        })
+    }
+}
+
+impl error::Error for ParseError {}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ParseErrorKind::ExpectedTerm => f.write_str("expected a term")?,
+            ParseErrorKind::UnclosedParen => f.write_str("unclosed parenthesis")?,
+            ParseErrorKind::UnmatchedParen => f.write_str("unmatched closing parenthesis")?,
+            ParseErrorKind::UnterminatedQuote => f.write_str("unterminated double quote")?,
+            ParseErrorKind::UnknownOperator(ref name) => write!(f, "unknown operator `{}:`", name)?,
+            ParseErrorKind::InvalidValue(ref name) => {
+                write!(f, "invalid value for operator `{}:`", name)?
+            }
+        }
+        write!(f, " at byte {}", self.offset)
+    }
+}
+
+impl error::Error for ValidationError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            ValidationError::Parse(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            ValidationError::Parse(ref e) => Display::fmt(e, f),
+            ValidationError::TooLong { len, max } => {
+                write!(f, "rule too long ({} characters, maximum is {})", len, max)
+            }
+            ValidationError::Unavailable { operator, tier } => {
+                let colon = if operator == "$" || operator.contains(':') {
+                    ""
+                } else {
+                    ":"
+                };
+                write!(
+                    f,
+                    "operator `{}{}` is not available with {} access",
+                    operator, colon, tier
+                )
+            }
+            ValidationError::MissingStandaloneTerm => {
+                f.write_str("rule must contain a positive standalone term")
+            }
+            ValidationError::MustBeNegated(ref op) => {
+                write!(f, "operator `{}` can only be used negated", op)
+            }
+            ValidationError::AreaOutOfRange => f.write_str("geographic area out of range"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(s: &str) -> Expr {
+        Expr::parse(s).unwrap()
+    }
+
+    fn kw(s: &str) -> Expr {
+        Expr::Keyword(s.to_string())
+    }
+
+    fn op(op: Operator, value: &str) -> Expr {
+        Expr::Operator(op, value.to_string())
+    }
+
+    #[test]
+    fn parse_terms() {
+        assert_eq!(parse("cat"), kw("cat"));
+        assert_eq!(
+            parse(r#""happy \"birthday\"""#),
+            Expr::Phrase(r#"happy "birthday""#.to_string()),
+        );
+        assert_eq!(
+            parse("#Rust @TwitterDev $TWTR 🐈"),
+            Expr::And(vec![
+                Expr::Hashtag("Rust".to_string()),
+                Expr::Mention("TwitterDev".to_string()),
+                Expr::Cashtag("TWTR".to_string()),
+                kw("🐈"),
+            ]),
+        );
+        assert_eq!(
+            parse(r#"from:TwitterDev url:"https://developer.twitter.com" lang:en"#),
+            Expr::And(vec![
+                op(Operator::From, "TwitterDev"),
+                op(Operator::Url, "https://developer.twitter.com"),
+                op(Operator::Lang, "en"),
+            ]),
+        );
+        assert_eq!(
+            parse("point_radius:[2.355128 48.861118 16km]"),
+            Expr::PointRadius(PointRadius {
+                longitude: 2.355128,
+                latitude: 48.861118,
+                radius: 16.,
+                unit: DistanceUnit::Kilometers,
+            }),
+        );
+        assert_eq!(
+            parse("bounding_box:[-105.301758 39.964069 -105.178505 40.09455]"),
+            Expr::BoundingBox(BoundingBox {
+                west: -105.301758,
+                south: 39.964069,
+                east: -105.178505,
+                north: 40.09455,
+            }),
+        );
+    }
+
+    #[test]
+    fn parse_operators() {
+        // AND binds tighter than OR.
+        assert_eq!(
+            parse("cat dog OR bird"),
+            Expr::Or(vec![Expr::And(vec![kw("cat"), kw("dog")]), kw("bird")]),
+        );
+        assert_eq!(
+            parse("(cat OR dog) -is:retweet"),
+            Expr::And(vec![
+                Expr::Or(vec![kw("cat"), kw("dog")]),
+                Expr::Not(Box::new(op(Operator::Is, "retweet"))),
+            ]),
+        );
+        assert_eq!(
+            parse("-(grumpy OR angry) cat"),
+            Expr::And(vec![
+                Expr::Not(Box::new(Expr::Or(vec![kw("grumpy"), kw("angry")]))),
+                kw("cat"),
+            ]),
+        );
+        // Only an uppercase `OR` at a word boundary is an operator.
+        assert_eq!(
+            parse("ORANGE or - OR(x)"),
+            Expr::Or(vec![
+                Expr::And(vec![kw("ORANGE"), kw("or"), kw("-")]),
+                kw("x")
+            ]),
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        fn error(s: &str) -> (ParseErrorKind, usize) {
+            let e = Expr::parse(s).unwrap_err();
+            (e.kind().clone(), e.offset())
+        }
+
+        assert_eq!(error(""), (ParseErrorKind::ExpectedTerm, 0));
+        assert_eq!(error("cat OR"), (ParseErrorKind::ExpectedTerm, 6));
+        assert_eq!(error("OR cat"), (ParseErrorKind::ExpectedTerm, 0));
+        assert_eq!(error("cat ()"), (ParseErrorKind::ExpectedTerm, 5));
+        assert_eq!(error("cat (dog"), (ParseErrorKind::UnclosedParen, 4));
+        assert_eq!(error("cat) dog"), (ParseErrorKind::UnmatchedParen, 3));
+        assert_eq!(error(r#"cat "dog"#), (ParseErrorKind::UnterminatedQuote, 4));
+        assert_eq!(
+            error("cat form:jack"),
+            (ParseErrorKind::UnknownOperator("form".to_string()), 4),
+        );
+        assert_eq!(
+            error("cat has:image"),
+            (ParseErrorKind::InvalidValue("has".to_string()), 4),
+        );
+        assert_eq!(
+            error("cat sample:101"),
+            (ParseErrorKind::InvalidValue("sample".to_string()), 4),
+        );
+        assert_eq!(
+            error("point_radius:[1 2 3]"),
+            (ParseErrorKind::InvalidValue("point_radius".to_string()), 0),
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let rules = [
+            "cat",
+            "cat has:images -is:retweet",
+            "(cat OR dog) (has:images OR has:videos) lang:en",
+            "a b OR c d OR -(e f)",
+            "(a OR b) OR c",
+            "(a b) c",
+            r#""exact \"phrase\"" place:"new york city" url:"a\\b""#,
+            "#Rust OR @rustlang OR $RUST",
+            "point_radius:[-105.27346517 40.01924738 0.5mi] bounding_box:[-1 -2.5 1 2.5]",
+            "from:TwitterDev OR to:TwitterDev OR retweets_of:TwitterDev sample:10",
+        ];
+        for &rule in &rules {
+            let expr = parse(rule);
+            assert_eq!(expr.to_string(), rule);
+            assert_eq!(parse(&expr.to_string()), expr);
+        }
+
+        // Redundant parentheses and whitespace are normalized.
+        assert_eq!(parse("  ((cat))   OR   ( dog )").to_string(), "cat OR dog");
+        assert_eq!(
+            Expr::Operator(Operator::Place, "[x]".to_string()).to_string(),
+            r#"place:"[x]""#,
+        );
+    }
+
+    #[test]
+    fn validation() {
+        use self::AccessTier::*;
+
+        assert!(validate("cat has:images", Essential).is_ok());
+        assert_eq!(
+            validate("has:images", Essential),
+            Err(ValidationError::MissingStandaloneTerm),
+        );
+        assert_eq!(
+            validate("-cat -dog", Essential),
+            Err(ValidationError::MissingStandaloneTerm),
+        );
+        assert_eq!(
+            validate("cat OR has:images", Essential),
+            Err(ValidationError::MissingStandaloneTerm),
+        );
+        assert!(validate("(cat OR dog) has:images", Essential).is_ok());
+        assert_eq!(
+            validate("cat is:nullcast", Essential),
+            Err(ValidationError::MustBeNegated("is:nullcast".to_string())),
+        );
+        assert!(validate("cat -is:nullcast", Essential).is_ok());
+
+        assert_eq!(
+            validate("cat place_country:US", Elevated),
+            Err(ValidationError::Unavailable {
+                operator: "place_country",
+                tier: Elevated,
+            }),
+        );
+        assert!(validate("cat place_country:US", AcademicResearch).is_ok());
+        for &(rule, operator) in &[
+            ("$TWTR", "$"),
+            ("cat -$TWTR", "$"),
+            ("cat has:cashtags", "has:cashtags"),
+            ("cat has:geo", "has:geo"),
+        ] {
+            let unavailable = ValidationError::Unavailable {
+                operator,
+                tier: Essential,
+            };
+            assert_eq!(validate(rule, Essential), Err(unavailable));
+            assert!(validate(rule, AcademicResearch).is_ok());
+        }
+        assert_eq!(
+            ValidationError::Unavailable {
+                operator: "has:geo",
+                tier: Essential,
+            }
+            .to_string(),
+            format!(
+                "operator `has:geo` is not available with {} access",
+                Essential
+            ),
+        );
+        assert!(validate("point_radius:[2.35 48.86 16km]", AcademicResearch).is_ok());
+        assert_eq!(
+            validate("point_radius:[2.35 48.86 41km]", AcademicResearch),
+            Err(ValidationError::AreaOutOfRange),
+        );
+        assert_eq!(
+            validate("bounding_box:[0 0 1 1]", AcademicResearch),
+            Err(ValidationError::AreaOutOfRange),
+        );
+
+        let long = vec!["cat"; 200].join(" ");
+        assert_eq!(
+            validate(&long, Elevated),
+            Err(ValidationError::TooLong { len: 799, max: 512 }),
+        );
+        assert!(validate(&long, AcademicResearch).is_ok());
+
+        match validate("cat (", Essential) {
+            Err(ValidationError::Parse(_)) => {}
+            res => panic!("{:?}", res),
+        }
+    }
+}
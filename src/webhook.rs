@@ -0,0 +1,523 @@
+//! Account Activity API webhooks.
+//!
+//! Twitter delivers the account activities to a webhook URL registered for the app,
+//! as `POST` requests signed with the consumer secret. It also periodically sends
+//! a Challenge-Response Check (CRC) as a `GET` request, which must be answered with
+//! an HMAC-SHA256 of the `crc_token` parameter.
+//!
+//! `Webhook` handles both kinds of requests and forwards the verified events to
+//! an `Events` stream. With the `hyper` feature, `bind` runs a local HTTP server with them
+//! and returns a `WebhookStream`, whose items are of the same type as those of
+//! a `TwitterStream`.
+//!
+//! See the [Twitter Developer Documentation][1] for more information.
+//!
+//! [1]: https://developer.twitter.com/en/docs/twitter-api/enterprise/account-activity-api/guides/securing-webhooks
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use futures::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let addr = ([127, 0, 0, 1], 8080).into();
+//! twitter_stream::webhook::bind(&addr, "consumer_secret")
+//!     .unwrap()
+//!     .try_for_each(|json| {
+//!         println!("{}", json);
+//!         future::ok(())
+//!     })
+//!     .await
+//!     .unwrap();
+//! # }
+//! ```
+
+use std::borrow::Borrow;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::str;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures_channel::mpsc;
+use futures_core::Stream;
+use futures_util::future::poll_fn;
+use hmac::{Hmac, Mac, NewMac};
+use http::header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response};
+use http_body::Body;
+use sha2::Sha256;
+
+use crate::types::{RequestMethod, StatusCode};
+use crate::util::*;
+
+/// The header containing the signature of an event.
+pub const SIGNATURE_HEADER: &str = "x-twitter-webhooks-signature";
+
+/// The default maximum length in bytes of an event request body (1 MiB).
+pub const DEFAULT_MAX_BODY_LEN: usize = 1024 * 1024;
+
+/// The number of events buffered in the channel between a `Webhook` and its `Events`.
+const EVENT_BUFFER: usize = 16;
+
+/// A handler of webhook requests.
+///
+/// This answers CRC requests and forwards the events with a valid signature to
+/// the `Events` stream paired with it.
+///
+/// The requests are unauthenticated until their signature is checked, so a body longer
+/// than `max_body_len` is rejected without being buffered as a whole.
+#[derive(Clone)]
+pub struct Webhook<S = String> {
+    consumer_secret: S,
+    max_body_len: usize,
+    tx: mpsc::Sender<string::String<Bytes>>,
+}
+
+/// A `Stream` of the events received by a `Webhook`.
+///
+/// Each item is the JSON body of an event request, which may contain multiple
+/// activities (e.g. `tweet_create_events`) for the subscribed user (`for_user_id`).
+#[derive(Debug)]
+pub struct Events {
+    rx: mpsc::Receiver<string::String<Bytes>>,
+}
+
+/// Create a `Webhook` that verifies requests with `consumer_secret`, and the `Events`
+/// stream to which it forwards the events.
+///
+/// The channel buffers a few events. When it is full, `Webhook::handle` waits for
+/// the `Events` to be polled before answering, so that an event is not acknowledged to
+/// Twitter before there is room for it.
+///
+/// The stream ends when all the clones of the `Webhook` are dropped.
+pub fn channel<S: Borrow<str>>(consumer_secret: S) -> (Webhook<S>, Events) {
+    let (tx, rx) = mpsc::channel(EVENT_BUFFER);
+    (
+        Webhook {
+            consumer_secret,
+            max_body_len: DEFAULT_MAX_BODY_LEN,
+            tx,
+        },
+        Events { rx },
+    )
+}
+
+/// Compute the `response_token` for a CRC request, in the form of `sha256=<base64>`.
+pub fn crc_response(crc_token: &str, consumer_secret: &str) -> String {
+    let mut mac = hmac_sha256(consumer_secret);
+    mac.update(crc_token.as_bytes());
+    format!("sha256={}", base64::encode(mac.finalize().into_bytes()))
+}
+
+/// Check that `signature` (the value of the `x-twitter-webhooks-signature` header) is
+/// a valid signature of `body`.
+///
+/// The signature is compared in constant time.
+pub fn verify_signature(signature: &[u8], body: &[u8], consumer_secret: &str) -> bool {
+    let signature = match signature.strip_prefix(b"sha256=") {
+        Some(s) => s,
+        None => return false,
+    };
+    let signature = match base64::decode(signature) {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+    let mut mac = hmac_sha256(consumer_secret);
+    mac.update(body);
+    mac.verify(&signature).is_ok()
+}
+
+fn hmac_sha256(key: &str) -> Hmac<Sha256> {
+    // HMAC accepts a key of any length.
+    Hmac::new_varkey(key.as_bytes()).unwrap()
+}
+
+impl<S: Borrow<str>> Webhook<S> {
+    /// Set the maximum length in bytes of an event request body.
+    ///
+    /// A longer request is rejected with `413 Payload Too Large`.
+    /// The default is `DEFAULT_MAX_BODY_LEN`.
+    pub fn max_body_len(&mut self, max_body_len: usize) -> &mut Self {
+        self.max_body_len = max_body_len;
+        self
+    }
+
+    /// Handle a request to the webhook URL.
+    ///
+    /// * `GET` requests with a `crc_token` parameter are answered with the `response_token`.
+    /// * `POST` requests with a valid signature are forwarded to `Events`.
+    ///   An invalid signature is rejected with `401 Unauthorized`, and a body longer than
+    ///   `max_body_len` with `413 Payload Too Large`.
+    pub fn handle<B: Body>(&self, req: Request<B>) -> impl Future<Output = Response<Vec<u8>>>
+    where
+        S: Clone,
+    {
+        let mut this = self.clone();
+        async move {
+            let (parts, body) = req.into_parts();
+            match parts.method {
+                RequestMethod::GET => this.crc(parts.uri.query().unwrap_or("")),
+                RequestMethod::POST => {
+                    let signature = match parts.headers.get(SIGNATURE_HEADER) {
+                        Some(s) => s.as_bytes(),
+                        None => return status(StatusCode::UNAUTHORIZED),
+                    };
+                    let len = parts
+                        .headers
+                        .get(CONTENT_LENGTH)
+                        .and_then(|len| len.to_str().ok()?.parse::<u64>().ok());
+                    match len {
+                        Some(len) if len > this.max_body_len as u64 => {
+                            return status(StatusCode::PAYLOAD_TOO_LARGE);
+                        }
+                        _ => {}
+                    }
+                    let body = match read_body_limited(body, this.max_body_len).await {
+                        Ok(Some(body)) => body,
+                        Ok(None) => return status(StatusCode::PAYLOAD_TOO_LARGE),
+                        Err(_) => return status(StatusCode::BAD_REQUEST),
+                    };
+                    this.event(signature, body).await
+                }
+                _ => status(StatusCode::METHOD_NOT_ALLOWED),
+            }
+        }
+    }
+
+    fn crc(&self, query: &str) -> Response<Vec<u8>> {
+        let crc_token = query
+            .split('&')
+            .filter_map(|pair| pair.strip_prefix("crc_token="))
+            .next()
+            .map(|token| percent_encoding::percent_decode_str(token).decode_utf8());
+        let crc_token = match crc_token {
+            Some(Ok(token)) => token,
+            _ => return status(StatusCode::BAD_REQUEST),
+        };
+
+        let body = json::json!({
+            "response_token": crc_response(&crc_token, self.consumer_secret.borrow()),
+        });
+        let mut res = Response::new(body.to_string().into_bytes());
+        res.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/json; charset=utf-8"),
+        );
+        res
+    }
+
+    async fn event(&mut self, signature: &[u8], body: Vec<u8>) -> Response<Vec<u8>> {
+        if !verify_signature(signature, &body, self.consumer_secret.borrow()) {
+            return status(StatusCode::UNAUTHORIZED);
+        }
+
+        let body = Bytes::from(body);
+        if str::from_utf8(&body).is_err() {
+            return status(StatusCode::BAD_REQUEST);
+        }
+        let event = unsafe { string::String::from_utf8_unchecked(body) };
+
+        // Wait for room in the channel.
+        let sent = match poll_fn(|cx| self.tx.poll_ready(cx)).await {
+            Ok(()) => self.tx.start_send(event),
+            Err(e) => Err(e),
+        };
+        if sent.is_err() {
+            // The `Events` has been dropped.
+            return status(StatusCode::SERVICE_UNAVAILABLE);
+        }
+        status(StatusCode::OK)
+    }
+}
+
+fn status(status: StatusCode) -> Response<Vec<u8>> {
+    let mut res = Response::new(Vec::new());
+    *res.status_mut() = status;
+    res
+}
+
+impl<S> Debug for Webhook<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Webhook")
+            .field("consumer_secret", &Hidden)
+            .field("max_body_len", &self.max_body_len)
+            .field("tx", &self.tx)
+            .finish()
+    }
+}
+
+impl Stream for Events {
+    type Item = string::String<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.rx).poll_next(cx)
+    }
+}
+
+#[cfg(feature = "hyper")]
+pub use self::server::{bind, WebhookStream};
+
+#[cfg(feature = "hyper")]
+mod server {
+    use std::future::Future;
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+
+    use bytes::Bytes;
+    use futures_core::Stream;
+    use futures_util::{FutureExt, TryFutureExt};
+    use hyper_pkg::service::{make_service_fn, service_fn};
+    use hyper_pkg::{Body, Server};
+
+    use super::Events;
+    use crate::error::Error;
+
+    type ServerFuture = Pin<Box<dyn Future<Output = Result<(), hyper_pkg::Error>> + Send>>;
+
+    /// A `Stream` of the events received by a local webhook server.
+    ///
+    /// The server runs as long as the stream is polled.
+    pub struct WebhookStream {
+        server: Option<ServerFuture>,
+        events: Events,
+        local_addr: SocketAddr,
+    }
+
+    /// Start a webhook server on `addr`, verifying requests with `consumer_secret`.
+    ///
+    /// The server must be exposed at the webhook URL registered for the app, which needs
+    /// to be HTTPS, e.g. through a reverse proxy.
+    pub fn bind(
+        addr: &SocketAddr,
+        consumer_secret: impl Into<String>,
+    ) -> Result<WebhookStream, hyper_pkg::Error> {
+        let (webhook, events) = super::channel(Arc::<str>::from(consumer_secret.into()));
+        let make_service = make_service_fn(move |_| {
+            let webhook = webhook.clone();
+            async move {
+                Ok::<_, hyper_pkg::Error>(service_fn(move |req| {
+                    webhook
+                        .handle::<Body>(req)
+                        .map(|res| Ok::<_, hyper_pkg::Error>(res.map(Body::from)))
+                }))
+            }
+        });
+
+        let server = Server::try_bind(addr)?.serve(make_service);
+        let local_addr = server.local_addr();
+
+        Ok(WebhookStream {
+            server: Some(Box::pin(server.map_ok(|_| ()))),
+            events,
+            local_addr,
+        })
+    }
+
+    impl WebhookStream {
+        /// Returns the address that the server is bound to.
+        pub fn local_addr(&self) -> SocketAddr {
+            self.local_addr
+        }
+    }
+
+    impl Stream for WebhookStream {
+        type Item = Result<string::String<Bytes>, Error<hyper_pkg::Error>>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            if let Some(ref mut server) = self.server {
+                if let Poll::Ready(result) = server.as_mut().poll(cx) {
+                    self.server = None;
+                    if let Err(e) = result {
+                        return Poll::Ready(Some(Err(Error::Service(e))));
+                    }
+                }
+            }
+
+            match Pin::new(&mut self.events).poll_next(cx) {
+                Poll::Ready(Some(event)) => Poll::Ready(Some(Ok(event))),
+                Poll::Ready(None) => Poll::Ready(None),
+                Poll::Pending if self.server.is_none() => Poll::Ready(None),
+                Poll::Pending => Poll::Pending,
+            }
+        }
+    }
+
+    impl std::fmt::Debug for WebhookStream {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("WebhookStream")
+                .field("events", &self.events)
+                .field("local_addr", &self.local_addr)
+                .finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures_executor::{block_on, block_on_stream};
+
+    use crate::util::ChunksBody;
+
+    const SECRET: &str = "consumer_secret";
+
+    fn request(
+        method: RequestMethod,
+        uri: &str,
+        body: &'static str,
+    ) -> Request<ChunksBody<std::iter::Once<Bytes>>> {
+        let body = ChunksBody(std::iter::once(Bytes::from_static(body.as_bytes())));
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(body)
+            .unwrap()
+    }
+
+    #[test]
+    fn crc() {
+        // printf crc | openssl dgst -sha256 -hmac consumer_secret -binary | base64
+        assert_eq!(
+            crc_response("crc", SECRET),
+            "sha256=EK25Vc0+EX89UoLQHVDfvSTdN50Wry68hNprkrdyCS0=",
+        );
+
+        let (webhook, _events) = channel(SECRET);
+        let res = block_on(webhook.handle(request(
+            RequestMethod::GET,
+            "https://example.com/webhook?crc_token=crc&nonce=1",
+            "",
+        )));
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: json::Value = json::from_slice(res.body()).unwrap();
+        assert_eq!(
+            body["response_token"],
+            "sha256=EK25Vc0+EX89UoLQHVDfvSTdN50Wry68hNprkrdyCS0="
+        );
+
+        let res = block_on(webhook.handle(request(RequestMethod::GET, "/webhook", "")));
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn events() {
+        const EVENT: &str = r#"{"for_user_id":"1","tweet_create_events":[]}"#;
+
+        let (webhook, events) = channel(SECRET.to_owned());
+        let signature = crc_response(EVENT, SECRET);
+
+        let mut req = request(RequestMethod::POST, "/webhook", EVENT);
+        req.headers_mut()
+            .insert(SIGNATURE_HEADER, signature.parse().unwrap());
+        assert_eq!(block_on(webhook.handle(req)).status(), StatusCode::OK);
+
+        let mut req = request(RequestMethod::POST, "/webhook", "{}");
+        req.headers_mut()
+            .insert(SIGNATURE_HEADER, signature.parse().unwrap());
+        assert_eq!(
+            block_on(webhook.handle(req)).status(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let req = request(RequestMethod::POST, "/webhook", EVENT);
+        assert_eq!(
+            block_on(webhook.handle(req)).status(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let req = request(RequestMethod::PUT, "/webhook", EVENT);
+        assert_eq!(
+            block_on(webhook.handle(req)).status(),
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+
+        drop(webhook);
+        let events: Vec<_> = block_on_stream(events).map(|e| e.to_string()).collect();
+        assert_eq!(events, [EVENT]);
+    }
+
+    #[test]
+    fn body_limit() {
+        const EVENT: &str = r#"{"for_user_id":"1"}"#;
+
+        let (mut webhook, events) = channel(SECRET);
+        webhook.max_body_len(EVENT.len());
+        let signature: HeaderValue = crc_response(EVENT, SECRET).parse().unwrap();
+
+        let mut req = request(RequestMethod::POST, "/webhook", EVENT);
+        req.headers_mut()
+            .insert(SIGNATURE_HEADER, signature.clone());
+        assert_eq!(block_on(webhook.handle(req)).status(), StatusCode::OK);
+
+        // Rejected by the `Content-Length` before reading the body.
+        let mut req = request(RequestMethod::POST, "/webhook", EVENT);
+        req.headers_mut()
+            .insert(SIGNATURE_HEADER, signature.clone());
+        req.headers_mut()
+            .insert(CONTENT_LENGTH, HeaderValue::from_static("1000000000"));
+        assert_eq!(
+            block_on(webhook.handle(req)).status(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+
+        // Rejected while reading the body.
+        let body = ChunksBody(
+            vec![
+                Bytes::from_static(EVENT.as_bytes()),
+                Bytes::from_static(b" "),
+            ]
+            .into_iter(),
+        );
+        let mut req = Request::post("/webhook").body(body).unwrap();
+        req.headers_mut().insert(SIGNATURE_HEADER, signature);
+        assert_eq!(
+            block_on(webhook.handle(req)).status(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+
+        drop(webhook);
+        assert_eq!(block_on_stream(events).count(), 1);
+    }
+
+    #[cfg(feature = "hyper")]
+    #[tokio::test]
+    async fn server() {
+        use futures_util::StreamExt;
+
+        const EVENT: &str = r#"{"for_user_id":"1"}"#;
+
+        let mut stream = bind(&([127, 0, 0, 1], 0).into(), SECRET).unwrap();
+        let uri = format!("http://{}/webhook", stream.local_addr());
+
+        let client = hyper_pkg::Client::new();
+        let req = Request::post(&*uri)
+            .header(SIGNATURE_HEADER, crc_response(EVENT, SECRET))
+            .body(hyper_pkg::Body::from(EVENT))
+            .unwrap();
+        let res = tokio::spawn(client.request(req));
+
+        let event = stream.next().await.unwrap().unwrap();
+        assert_eq!(&*event, EVENT);
+        assert_eq!(res.await.unwrap().unwrap().status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn signature() {
+        let signature = crc_response("body", SECRET);
+        assert!(verify_signature(signature.as_bytes(), b"body", SECRET));
+        assert!(!verify_signature(signature.as_bytes(), b"body", "other"));
+        assert!(!verify_signature(signature.as_bytes(), b"bodz", SECRET));
+        assert!(!verify_signature(
+            &signature.as_bytes()[7..],
+            b"body",
+            SECRET
+        ));
+        assert!(!verify_signature(b"sha256=!!!", b"body", SECRET));
+    }
+}